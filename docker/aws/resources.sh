@@ -3,5 +3,8 @@
 echo "Creating queue"
 awslocal sqs create-queue --queue-name test-queue
 
+echo "Creating dead-letter queue"
+awslocal sqs create-queue --queue-name test-dead-letter-queue
+
 echo "Creating bucket"
 awslocal s3api create-bucket --bucket test-bucket
@@ -0,0 +1,121 @@
+use std::sync::LazyLock;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use tokio::sync::broadcast::{self, Receiver, Sender};
+use utoipa::ToSchema;
+
+const CAPACITY: usize = 1024;
+
+static CHANNEL: LazyLock<Sender<Activity>> = LazyLock::new(|| broadcast::channel(CAPACITY).0);
+
+/// Something that happened in the pipeline, as streamed to `/events/stream`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Activity {
+    MessageReceived {
+        message_id: String,
+        notifications: usize,
+    },
+    NotificationProcessed {
+        message_id: String,
+        bucket: String,
+        key: String,
+        sources: Vec<String>,
+    },
+    NotificationFailed {
+        message_id: String,
+        bucket: String,
+        key: String,
+        sources: Vec<String>,
+        error: String,
+    },
+    BatchCreated {
+        source: String,
+        date: NaiveDate,
+    },
+    BatchFlushed {
+        source: String,
+        date: NaiveDate,
+        key: String,
+        records: usize,
+    },
+    MessageDeleted {
+        message_id: String,
+    },
+}
+
+impl Activity {
+    /// Message level activity has no source, so it only matches when no
+    /// source is asked for.
+    pub fn matches(&self, source: Option<&str>) -> bool {
+        let Some(source) = source else {
+            return true;
+        };
+        match self {
+            Activity::MessageReceived { .. } | Activity::MessageDeleted { .. } => false,
+            Activity::NotificationProcessed { sources, .. }
+            | Activity::NotificationFailed { sources, .. } => {
+                sources.iter().any(|candidate| candidate == source)
+            }
+            Activity::BatchCreated {
+                source: candidate, ..
+            }
+            | Activity::BatchFlushed {
+                source: candidate, ..
+            } => candidate == source,
+        }
+    }
+}
+
+/// Never waits: subscribers that fall behind miss activity instead of
+/// holding up the pipeline.
+pub fn publish(activity: Activity) {
+    // Sending only fails when nobody is subscribed.
+    let _ = CHANNEL.send(activity);
+}
+
+pub fn subscribe() -> Receiver<Activity> {
+    CHANNEL.subscribe()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_by_source() {
+        let flushed = Activity::BatchFlushed {
+            source: String::from("somewhere"),
+            date: NaiveDate::from_ymd_opt(2024, 8, 1).unwrap(),
+            key: String::from("key"),
+            records: 1,
+        };
+        let received = Activity::MessageReceived {
+            message_id: String::from("1234"),
+            notifications: 1,
+        };
+
+        assert!(flushed.matches(Some("somewhere")));
+        assert!(!flushed.matches(Some("elsewhere")));
+        assert!(received.matches(None));
+        assert!(!received.matches(Some("somewhere")));
+    }
+
+    #[tokio::test]
+    async fn delivers_published_activity() {
+        let mut receiver = subscribe();
+        let activity = Activity::MessageDeleted {
+            message_id: String::from("activity-test"),
+        };
+
+        publish(activity.clone());
+
+        // Other tests publish to the same channel concurrently.
+        loop {
+            if receiver.recv().await.unwrap() == activity {
+                break;
+            }
+        }
+    }
+}
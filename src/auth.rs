@@ -0,0 +1,358 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use axum::{
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+
+use crate::metrics;
+
+/// What a caller may do. `Admin` satisfies every other scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    Read,
+    Ingest,
+    Admin,
+}
+
+impl Scope {
+    fn name(&self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Ingest => "ingest",
+            Scope::Admin => "admin",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Scope> {
+        match name {
+            "read" => Some(Scope::Read),
+            "ingest" => Some(Scope::Ingest),
+            "admin" => Some(Scope::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// Checks a bearer token, returning the scopes it grants or `None` if this
+/// authenticator does not recognise it.
+pub trait Authenticator {
+    fn authenticate(&self, token: &str) -> Option<Vec<Scope>>;
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StaticToken {
+    token: String,
+    scopes: Vec<Scope>,
+}
+
+pub struct StaticTokens {
+    tokens: Vec<StaticToken>,
+}
+
+impl StaticTokens {
+    pub fn new(tokens: Vec<StaticToken>) -> Self {
+        Self { tokens }
+    }
+}
+
+impl Authenticator for StaticTokens {
+    fn authenticate(&self, token: &str) -> Option<Vec<Scope>> {
+        self.tokens
+            .iter()
+            .find(|candidate| constant_time_eq(candidate.token.as_bytes(), token.as_bytes()))
+            .map(|candidate| candidate.scopes.clone())
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct JwksConfig {
+    path: PathBuf,
+    issuer: Option<String>,
+    audience: Option<String>,
+}
+
+/// Scopes come from an OAuth style space separated `scope` claim or a
+/// `scopes` array.
+#[derive(Debug, Deserialize)]
+struct Claims {
+    #[serde(default)]
+    scope: String,
+    #[serde(default)]
+    scopes: Vec<String>,
+}
+
+pub struct Jwks {
+    keys: JwkSet,
+    issuer: Option<String>,
+    audience: Option<String>,
+}
+
+impl Jwks {
+    pub fn load(config: &JwksConfig) -> Self {
+        let content = fs::read_to_string(&config.path).expect("JWKS file should be readable");
+        Self {
+            keys: serde_json::from_str(&content).expect("JWKS file should be valid"),
+            issuer: config.issuer.clone(),
+            audience: config.audience.clone(),
+        }
+    }
+}
+
+impl Authenticator for Jwks {
+    fn authenticate(&self, token: &str) -> Option<Vec<Scope>> {
+        let header = jsonwebtoken::decode_header(token).ok()?;
+        let jwk = self.keys.find(header.kid.as_deref()?)?;
+        let key = DecodingKey::from_jwk(jwk).ok()?;
+
+        // The key decides the algorithm, never the token's own header. Keys
+        // without a usable `alg` verify nothing.
+        let Some(algorithm) = jwk
+            .common
+            .key_algorithm
+            .and_then(|algorithm| algorithm.to_string().parse::<Algorithm>().ok())
+        else {
+            tracing::debug!(
+                "Rejected JWT. Key '{}' has no signing algorithm",
+                jwk.common.key_id.as_deref().unwrap_or_default()
+            );
+            return None;
+        };
+        let mut validation = Validation::new(algorithm);
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+
+        let claims = match jsonwebtoken::decode::<Claims>(token, &key, &validation) {
+            Ok(data) => data.claims,
+            Err(e) => {
+                tracing::debug!("Rejected JWT. Error: {}", e);
+                return None;
+            }
+        };
+        Some(
+            claims
+                .scope
+                .split_whitespace()
+                .chain(claims.scopes.iter().map(String::as_str))
+                .filter_map(Scope::parse)
+                .collect(),
+        )
+    }
+}
+
+/// Tries each authenticator in turn. With none configured every protected
+/// request is rejected, unless auth has been explicitly disabled.
+pub struct Auth {
+    authenticators: Vec<Arc<dyn Authenticator + Send + Sync>>,
+    disabled: bool,
+}
+
+impl Auth {
+    pub fn new(authenticators: Vec<Arc<dyn Authenticator + Send + Sync>>) -> Self {
+        if authenticators.is_empty() {
+            tracing::error!(
+                "No authentication configured. Protected routes will reject every request"
+            );
+        }
+        Self {
+            authenticators,
+            disabled: false,
+        }
+    }
+
+    /// Allows every request, e.g. for local development.
+    pub fn disabled() -> Self {
+        tracing::warn!("Authentication is disabled. The HTTP API is open");
+        Self {
+            authenticators: Vec::new(),
+            disabled: true,
+        }
+    }
+
+    fn check(&self, request: &Request, required: Scope) -> Result<(), StatusCode> {
+        if self.disabled {
+            return Ok(());
+        }
+
+        let token = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+        let scopes = self
+            .authenticators
+            .iter()
+            .find_map(|authenticator| authenticator.authenticate(token))
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+
+        if scopes.contains(&required) || scopes.contains(&Scope::Admin) {
+            Ok(())
+        } else {
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+}
+
+/// Requires the scope for every route on the router.
+pub fn protect(router: Router, auth: Arc<Auth>, scope: Scope) -> Router {
+    router.route_layer(middleware::from_fn(move |request, next| {
+        require(auth.clone(), scope, request, next)
+    }))
+}
+
+/// Middleware rejecting requests whose token lacks the scope.
+async fn require(auth: Arc<Auth>, scope: Scope, request: Request, next: Next) -> Response {
+    match auth.check(&request, scope) {
+        Ok(()) => next.run(request).await,
+        Err(status) => {
+            metrics::increment(
+                "http_auth_rejections_total",
+                &[("scope", scope.name()), ("status", status.as_str())],
+            );
+            (status, [(header::WWW_AUTHENTICATE, "Bearer")]).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn admin_satisfies_every_scope() {
+        let auth = auth();
+
+        assert_eq!(auth.check(&request(Some("reader")), Scope::Read), Ok(()));
+        assert_eq!(
+            auth.check(&request(Some("reader")), Scope::Admin),
+            Err(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(auth.check(&request(Some("admin")), Scope::Ingest), Ok(()));
+    }
+
+    #[test]
+    fn rejects_missing_or_unknown_token() {
+        let auth = auth();
+
+        assert_eq!(
+            auth.check(&request(None), Scope::Read),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(
+            auth.check(&request(Some("other")), Scope::Read),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+    }
+
+    #[test]
+    fn rejects_everything_without_authenticators() {
+        let auth = Auth::new(Vec::new());
+        assert_eq!(
+            auth.check(&request(None), Scope::Read),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(
+            auth.check(&request(Some("anything")), Scope::Read),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+    }
+
+    #[test]
+    fn allows_everything_when_disabled() {
+        let auth = Auth::disabled();
+        assert_eq!(auth.check(&request(None), Scope::Admin), Ok(()));
+    }
+
+    #[test]
+    fn reads_scopes_from_signed_jwt() {
+        let jwks = Jwks {
+            keys: serde_json::from_value(json!({"keys": [
+                {"kty": "oct", "kid": "test", "alg": "HS256", "k": "c2VjcmV0"},
+            ]}))
+            .unwrap(),
+            issuer: Some(String::from("issuer")),
+            audience: None,
+        };
+        let claims = json!({"iss": "issuer", "exp": 4102444800u64, "scope": "read ingest"});
+        let header = Header {
+            kid: Some(String::from("test")),
+            ..Header::default()
+        };
+
+        let valid =
+            jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(b"secret")).unwrap();
+        let forged =
+            jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(b"forged")).unwrap();
+
+        assert_eq!(
+            jwks.authenticate(&valid),
+            Some(vec![Scope::Read, Scope::Ingest])
+        );
+        assert_eq!(jwks.authenticate(&forged), None);
+    }
+
+    #[test]
+    fn verifies_with_the_keys_algorithm() {
+        let jwks = Jwks {
+            keys: serde_json::from_value(json!({"keys": [
+                {"kty": "oct", "kid": "test", "alg": "HS256", "k": "c2VjcmV0"},
+                {"kty": "oct", "kid": "bare", "k": "c2VjcmV0"},
+            ]}))
+            .unwrap(),
+            issuer: None,
+            audience: None,
+        };
+        let claims = json!({"exp": 4102444800u64, "scope": "admin"});
+        let sign = |kid: &str, algorithm: Algorithm| {
+            let header = Header {
+                kid: Some(String::from(kid)),
+                ..Header::new(algorithm)
+            };
+            jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(b"secret")).unwrap()
+        };
+
+        assert_eq!(
+            jwks.authenticate(&sign("test", Algorithm::HS256)),
+            Some(vec![Scope::Admin])
+        );
+        assert_eq!(jwks.authenticate(&sign("test", Algorithm::HS512)), None);
+        assert_eq!(jwks.authenticate(&sign("bare", Algorithm::HS256)), None);
+    }
+
+    fn auth() -> Auth {
+        let tokens = serde_json::from_value(json!([
+            {"token": "reader", "scopes": ["read"]},
+            {"token": "admin", "scopes": ["admin"]},
+        ]))
+        .unwrap();
+        Auth::new(vec![Arc::new(StaticTokens::new(tokens))])
+    }
+
+    fn request(token: Option<&str>) -> Request {
+        let mut builder = Request::builder();
+        if let Some(token) = token {
+            builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+}
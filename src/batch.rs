@@ -1,11 +1,13 @@
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     sync::Mutex,
 };
 
 use chrono::{DateTime, NaiveDate, Utc};
 
-pub use summary::{Summariser, Summary};
+use crate::activity::{self, Activity};
+
+pub use summary::{Inspection, Page, Summariser, SummaryQuery, SummaryResponse};
 
 mod summary;
 
@@ -32,18 +34,25 @@ impl Partition {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Entry {
     partition: Partition,
     created: DateTime<Utc>,
+    message_id: String,
     json: String,
 }
 
 impl Entry {
-    pub fn new(partition: Partition, created: &DateTime<Utc>, json: &str) -> Self {
+    pub fn new(
+        partition: Partition,
+        created: &DateTime<Utc>,
+        message_id: &str,
+        json: &str,
+    ) -> Self {
         Self {
             partition,
             created: *created,
+            message_id: String::from(message_id),
             json: String::from(json),
         }
     }
@@ -54,6 +63,8 @@ pub struct Batch {
     partition: Partition,
     oldest_record: DateTime<Utc>,
     records: Vec<String>,
+    // The message each record came from, in the same order as the records.
+    message_ids: Vec<String>,
 }
 
 impl Batch {
@@ -72,6 +83,73 @@ impl Batch {
     pub fn record_count(&self) -> usize {
         self.records.len()
     }
+
+    fn counts(&self) -> Counts {
+        Counts {
+            partition: self.partition.clone(),
+            oldest_record: self.oldest_record,
+            record_count: self.records.len(),
+        }
+    }
+
+    fn push(&mut self, entry: Entry) {
+        self.records.push(entry.json);
+        self.message_ids.push(entry.message_id);
+        if entry.created < self.oldest_record {
+            self.oldest_record = entry.created;
+        };
+    }
+}
+
+/// The size and age of a batch, without its records.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Counts {
+    partition: Partition,
+    oldest_record: DateTime<Utc>,
+    record_count: usize,
+}
+
+impl Counts {
+    pub fn partition(&self) -> &Partition {
+        &self.partition
+    }
+
+    pub fn oldest_record(&self) -> &DateTime<Utc> {
+        &self.oldest_record
+    }
+
+    pub fn record_count(&self) -> usize {
+        self.record_count
+    }
+
+    fn add(&mut self, entry: &Entry) {
+        self.record_count += 1;
+        if entry.created < self.oldest_record {
+            self.oldest_record = entry.created;
+        }
+    }
+}
+
+/// A page of one batch's records, with every message id that contributed.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Preview {
+    counts: Counts,
+    message_ids: Vec<String>,
+    records: Vec<String>,
+}
+
+impl Preview {
+    pub fn counts(&self) -> &Counts {
+        &self.counts
+    }
+
+    pub fn message_ids(&self) -> &[String] {
+        &self.message_ids
+    }
+
+    pub fn records(&self) -> &[String] {
+        &self.records
+    }
 }
 
 pub trait Store {
@@ -79,12 +157,25 @@ pub trait Store {
 
     fn batches(&self) -> Vec<Batch>;
 
-    fn delete_batch(&self, partition: &Partition);
+    /// The size and age of each batch, counting queued entries, without
+    /// copying any records.
+    fn counts(&self) -> Vec<Counts>;
+
+    /// A page of the partition's records, including queued entries. Only the
+    /// page is copied.
+    fn preview(&self, partition: &Partition, offset: usize, limit: usize) -> Option<Preview>;
+
+    /// Removes the first `count` records of the partition's batch, keeping any
+    /// added since it was read.
+    fn delete_records(&self, partition: &Partition, count: usize);
 }
 
 pub struct StoreImpl {
     queue: Mutex<VecDeque<Entry>>,
     batches: Mutex<HashMap<Partition, Batch>>,
+    // Partitions with a batch or queued entries, so a new batch is announced
+    // as its first entry arrives. Locked after the queue and batches.
+    partitions: Mutex<HashSet<Partition>>,
 }
 
 impl StoreImpl {
@@ -92,13 +183,26 @@ impl StoreImpl {
         Self {
             queue: Mutex::new(VecDeque::new()),
             batches: Mutex::new(HashMap::new()),
+            partitions: Mutex::new(HashSet::new()),
         }
     }
 }
 
 impl Store for StoreImpl {
     fn add(&self, entry: Entry) {
-        self.queue.lock().unwrap().push_back(entry);
+        let mut queue_lock = self.queue.lock().unwrap();
+        if self
+            .partitions
+            .lock()
+            .unwrap()
+            .insert(entry.partition.clone())
+        {
+            activity::publish(Activity::BatchCreated {
+                source: String::from(entry.partition.source()),
+                date: *entry.partition.date(),
+            });
+        }
+        queue_lock.push_back(entry);
     }
 
     fn batches(&self) -> Vec<Batch> {
@@ -108,26 +212,183 @@ impl Store for StoreImpl {
         let mut batches_lock = self.batches.lock().unwrap();
 
         for entry in entries {
-            let batch = batches_lock
-                .entry(entry.partition)
-                .or_insert_with_key(|key| Batch {
-                    partition: key.clone(),
-                    oldest_record: Utc::now(),
-                    records: Vec::new(),
-                });
-
-            batch.records.push(entry.json);
-            if entry.created < batch.oldest_record {
-                batch.oldest_record = entry.created;
-            };
+            append(&mut batches_lock, entry);
         }
 
         batches_lock.values().cloned().collect()
     }
 
-    fn delete_batch(&self, partition: &Partition) {
-        tracing::info!("Deleting batch '{:?}'", partition);
+    fn counts(&self) -> Vec<Counts> {
+        let queue_lock = self.queue.lock().unwrap();
+        let batches_lock = self.batches.lock().unwrap();
+
+        let mut counts: HashMap<&Partition, Counts> = batches_lock
+            .iter()
+            .map(|(partition, batch)| (partition, batch.counts()))
+            .collect();
+        for entry in queue_lock.iter() {
+            counts
+                .entry(&entry.partition)
+                .or_insert_with(|| empty_counts(&entry.partition))
+                .add(entry);
+        }
+
+        counts.into_values().collect()
+    }
+
+    fn preview(&self, partition: &Partition, offset: usize, limit: usize) -> Option<Preview> {
+        let queue_lock = self.queue.lock().unwrap();
+        let batches_lock = self.batches.lock().unwrap();
+
+        let batch = batches_lock.get(partition);
+        let queued: Vec<&Entry> = queue_lock
+            .iter()
+            .filter(|entry| &entry.partition == partition)
+            .collect();
+        if batch.is_none() && queued.is_empty() {
+            return None;
+        }
+
+        let mut counts = batch.map_or_else(|| empty_counts(partition), Batch::counts);
+        for entry in &queued {
+            counts.add(entry);
+        }
+        let (batch_records, batch_message_ids) = match batch {
+            Some(batch) => (batch.records.as_slice(), batch.message_ids.as_slice()),
+            None => (&[][..], &[][..]),
+        };
+        let records = batch_records
+            .iter()
+            .chain(queued.iter().map(|entry| &entry.json))
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect();
+        let message_ids = batch_message_ids
+            .iter()
+            .chain(queued.iter().map(|entry| &entry.message_id))
+            .map(String::as_str);
+
+        Some(Preview {
+            counts,
+            message_ids: unique(message_ids).into_iter().map(String::from).collect(),
+            records,
+        })
+    }
+
+    fn delete_records(&self, partition: &Partition, count: usize) {
+        tracing::info!("Deleting {} records from batch '{:?}'", count, partition);
+        let queue_lock = self.queue.lock().unwrap();
         let mut batches_lock = self.batches.lock().unwrap();
-        batches_lock.remove(partition);
+        let Some(batch) = batches_lock.get_mut(partition) else {
+            return;
+        };
+
+        // The oldest record time is kept for any remaining records, which only
+        // makes the batch ready sooner.
+        let count = count.min(batch.records.len());
+        batch.records.drain(..count);
+        batch.message_ids.drain(..count);
+        if batch.records.is_empty() {
+            batches_lock.remove(partition);
+            if !queue_lock.iter().any(|entry| &entry.partition == partition) {
+                self.partitions.lock().unwrap().remove(partition);
+            }
+        }
+    }
+}
+
+// Matches a new batch in `append`, which starts from now until a record is
+// older.
+fn empty_counts(partition: &Partition) -> Counts {
+    Counts {
+        partition: partition.clone(),
+        oldest_record: Utc::now(),
+        record_count: 0,
+    }
+}
+
+/// Drops repeats, keeping the first occurrence of each.
+fn unique<'a>(values: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut seen = HashSet::new();
+    values.filter(|value| seen.insert(*value)).collect()
+}
+
+fn append(batches: &mut HashMap<Partition, Batch>, entry: Entry) {
+    batches
+        .entry(entry.partition.clone())
+        .or_insert_with_key(|key| Batch {
+            partition: key.clone(),
+            oldest_record: Utc::now(),
+            records: Vec::new(),
+            message_ids: Vec::new(),
+        })
+        .push(entry);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn announces_batches_as_their_first_entry_arrives() {
+        let mut receiver = activity::subscribe();
+        let store = StoreImpl::new();
+        let partition = Partition::new("announced", Utc::now().date_naive());
+
+        store.add(Entry::new(partition.clone(), &Utc::now(), "a", "1"));
+        store.add(Entry::new(partition.clone(), &Utc::now(), "b", "2"));
+        store.batches();
+        store.delete_records(&partition, 2);
+        store.add(Entry::new(partition.clone(), &Utc::now(), "c", "3"));
+        activity::publish(Activity::MessageDeleted {
+            message_id: String::from("announced-end"),
+        });
+
+        // Other tests publish to the same channel concurrently.
+        let mut created = 0;
+        loop {
+            match receiver.recv().await.unwrap() {
+                Activity::BatchCreated { source, .. } if source == "announced" => created += 1,
+                Activity::MessageDeleted { message_id } if message_id == "announced-end" => break,
+                _ => {}
+            }
+        }
+        assert_eq!(created, 2);
+    }
+
+    #[test]
+    fn keeps_records_added_after_read() {
+        let store = StoreImpl::new();
+        let partition = Partition::new("somewhere", Utc::now().date_naive());
+        store.add(Entry::new(partition.clone(), &Utc::now(), "a", "1"));
+        let read = store.batches();
+        store.add(Entry::new(partition.clone(), &Utc::now(), "b", "2"));
+        store.batches();
+
+        store.delete_records(&partition, read[0].record_count());
+
+        assert_eq!(store.batches()[0].records(), &[String::from("2")]);
+        store.delete_records(&partition, 1);
+        assert!(store.batches().is_empty());
+    }
+
+    #[test]
+    fn reads_leave_queue_in_place() {
+        let store = StoreImpl::new();
+        let partition = Partition::new("somewhere", Utc::now().date_naive());
+        store.add(Entry::new(partition.clone(), &Utc::now(), "a", "1"));
+        store.batches();
+        store.add(Entry::new(partition.clone(), &Utc::now(), "a", "2"));
+        store.add(Entry::new(partition.clone(), &Utc::now(), "b", "3"));
+
+        let counts = store.counts();
+        let preview = store.preview(&partition, 1, 1).unwrap();
+
+        assert_eq!(counts[0].record_count(), 3);
+        assert_eq!(preview.counts(), &counts[0]);
+        assert_eq!(preview.message_ids(), &["a", "b"]);
+        assert_eq!(preview.records(), &["2"]);
+        assert_eq!(store.queue.lock().unwrap().len(), 2);
     }
 }
@@ -1,9 +1,14 @@
-use std::sync::Arc;
+use std::{cmp::Reverse, sync::Arc};
 
 use chrono::{DateTime, NaiveDate, Utc};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Debug, Serialize)]
+const DEFAULT_PAGE_SIZE: usize = 20;
+const MAX_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct Summary {
     source: String,
     date: NaiveDate,
@@ -11,6 +16,127 @@ pub struct Summary {
     record_count: usize,
 }
 
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
+pub struct Page {
+    offset: usize,
+    limit: usize,
+}
+
+impl Default for Page {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            limit: DEFAULT_PAGE_SIZE,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    #[default]
+    Source,
+    Age,
+    Count,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Order {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Filters on source prefix and an inclusive date range, then sorts and pages
+/// the summaries. Ascending age puts the newest batches first.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
+pub struct SummaryQuery {
+    source_prefix: Option<String>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    sort: Option<SortBy>,
+    order: Option<Order>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+impl SummaryQuery {
+    /// Without parameters the summary keeps its original bare array shape.
+    pub fn is_empty(&self) -> bool {
+        self.source_prefix.is_none()
+            && self.from.is_none()
+            && self.to.is_none()
+            && self.sort.is_none()
+            && self.order.is_none()
+            && self.offset.is_none()
+            && self.limit.is_none()
+    }
+
+    fn matches(&self, summary: &Summary) -> bool {
+        let source_matches = match &self.source_prefix {
+            Some(prefix) => summary.source.starts_with(prefix.as_str()),
+            None => true,
+        };
+        let from_matches = match &self.from {
+            Some(from) => summary.date >= *from,
+            None => true,
+        };
+        let to_matches = match &self.to {
+            Some(to) => summary.date <= *to,
+            None => true,
+        };
+        source_matches && from_matches && to_matches
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Totals {
+    partitions: usize,
+    records: usize,
+}
+
+impl Totals {
+    fn of(summaries: &[Summary]) -> Self {
+        Self {
+            partitions: summaries.len(),
+            records: summaries.iter().map(|summary| summary.record_count).sum(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SummaryPage {
+    total: Totals,
+    matched: Totals,
+    offset: usize,
+    limit: usize,
+    summaries: Vec<Summary>,
+}
+
+/// The bare array is kept for callers that pass no query parameters.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum SummaryResponse {
+    Summaries(Vec<Summary>),
+    Page(SummaryPage),
+}
+
+/// A buffered batch with a page of its records, as they will be written.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Inspection {
+    #[serde(flatten)]
+    summary: Summary,
+    message_ids: Vec<String>,
+    offset: usize,
+    limit: usize,
+    records: Vec<Value>,
+}
+
 pub struct Summariser {
     batch_store: Arc<dyn super::Store + Sync + Send>,
 }
@@ -21,16 +147,66 @@ impl Summariser {
     }
 
     pub fn summary(&self) -> Vec<Summary> {
-        self.batch_store
-            .batches()
+        let mut summaries: Vec<Summary> = self
+            .batch_store
+            .counts()
             .into_iter()
             .map(From::from)
-            .collect()
+            .collect();
+        summaries.sort_by(|a, b| (&a.source, a.date).cmp(&(&b.source, b.date)));
+        summaries
+    }
+
+    pub fn query(&self, query: &SummaryQuery) -> SummaryPage {
+        let all = self.summary();
+        let mut matched: Vec<Summary> = all
+            .iter()
+            .filter(|summary| query.matches(summary))
+            .cloned()
+            .collect();
+
+        match query.sort.unwrap_or_default() {
+            SortBy::Source => {}
+            SortBy::Age => matched.sort_by_key(|summary| Reverse(summary.oldest_record)),
+            SortBy::Count => matched.sort_by_key(|summary| summary.record_count),
+        }
+        if query.order.unwrap_or_default() == Order::Desc {
+            matched.reverse();
+        }
+
+        let offset = query.offset.unwrap_or(0);
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+        SummaryPage {
+            total: Totals::of(&all),
+            matched: Totals::of(&matched),
+            offset,
+            limit,
+            summaries: matched.into_iter().skip(offset).take(limit).collect(),
+        }
+    }
+
+    pub fn inspect(&self, partition: &super::Partition, page: &Page) -> Option<Inspection> {
+        let limit = page.limit.min(MAX_PAGE_SIZE);
+        let preview = self.batch_store.preview(partition, page.offset, limit)?;
+
+        let records = preview
+            .records()
+            .iter()
+            .map(|record| serde_json::from_str(record).unwrap_or(Value::Null))
+            .collect();
+
+        Some(Inspection {
+            summary: Summary::from(preview.counts().clone()),
+            message_ids: preview.message_ids().to_vec(),
+            offset: page.offset,
+            limit,
+            records,
+        })
     }
 }
 
-impl From<super::Batch> for Summary {
-    fn from(value: super::Batch) -> Self {
+impl From<super::Counts> for Summary {
+    fn from(value: super::Counts) -> Self {
         Self {
             source: value.partition().source().to_owned(),
             date: value.partition().date().to_owned(),
@@ -39,3 +215,97 @@ impl From<super::Batch> for Summary {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::batch::{Entry, Partition, Store, StoreImpl};
+
+    #[test]
+    fn previews_page_of_records() {
+        let store = Arc::new(StoreImpl::new());
+        let partition = Partition::new("somewhere", Utc::now().date_naive());
+        for (message_id, record) in [("a", r#"{"id":"1"}"#), ("b", r#"{"id":"2"}"#)] {
+            store.add(Entry::new(
+                partition.clone(),
+                &Utc::now(),
+                message_id,
+                record,
+            ));
+        }
+        let summariser = Summariser::new(store.clone());
+
+        let actual = summariser
+            .inspect(
+                &partition,
+                &Page {
+                    offset: 1,
+                    limit: 5,
+                },
+            )
+            .unwrap();
+
+        assert_eq!(actual.summary.record_count, 2);
+        assert_eq!(actual.message_ids, vec!["a", "b"]);
+        assert_eq!(actual.records, vec![json!({"id": "2"})]);
+        assert_eq!(store.batches()[0].record_count(), 2);
+    }
+
+    #[test]
+    fn filters_sorts_and_pages_summaries() {
+        let store = Arc::new(StoreImpl::new());
+        let date = |day| NaiveDate::from_ymd_opt(2024, 8, day).unwrap();
+        for (source, day, count) in [
+            ("web-a", 1, 3),
+            ("web-b", 2, 1),
+            ("web-c", 3, 2),
+            ("app", 2, 5),
+        ] {
+            for _ in 0..count {
+                store.add(Entry::new(
+                    Partition::new(source, date(day)),
+                    &Utc::now(),
+                    "a",
+                    "{}",
+                ));
+            }
+        }
+        let summariser = Summariser::new(store);
+        let query: SummaryQuery = serde_json::from_value(json!({
+            "source_prefix": "web-",
+            "to": "2024-08-02",
+            "sort": "count",
+            "order": "desc",
+            "limit": 1,
+        }))
+        .unwrap();
+
+        let actual = summariser.query(&query);
+
+        assert_eq!(
+            actual.total,
+            Totals {
+                partitions: 4,
+                records: 11
+            }
+        );
+        assert_eq!(
+            actual.matched,
+            Totals {
+                partitions: 2,
+                records: 4
+            }
+        );
+        let sources: Vec<&str> = actual.summaries.iter().map(|s| s.source.as_str()).collect();
+        assert_eq!(sources, vec!["web-a"]);
+    }
+
+    #[test]
+    fn inspects_missing_batch() {
+        let summariser = Summariser::new(Arc::new(StoreImpl::new()));
+        let partition = Partition::new("somewhere", Utc::now().date_naive());
+        assert!(summariser.inspect(&partition, &Page::default()).is_none());
+    }
+}
@@ -0,0 +1,604 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    auth::{JwksConfig, StaticToken},
+    limits::LimitsConfig,
+    model::EventType,
+    processor::{CollisionPolicy, DetectorRule, FieldRule, KeyScheme, ReservedFields, Rule},
+    server::{ListenerConfig, TlsConfig},
+};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    handler: HandlerConfig,
+    visibility: VisibilityConfig,
+    notifications: NotificationsConfig,
+    extractor: ExtractorConfig,
+    transform: TransformConfig,
+    mapping: MappingConfig,
+    redaction: RedactionConfig,
+    schemas: SchemasConfig,
+    dedup: DedupConfig,
+    reload: ReloadConfig,
+    auth: AuthConfig,
+    server: ServerConfig,
+    http: HttpConfig,
+}
+
+impl Config {
+    pub fn handler(&self) -> &HandlerConfig {
+        &self.handler
+    }
+
+    pub fn visibility(&self) -> &VisibilityConfig {
+        &self.visibility
+    }
+
+    pub fn notifications(&self) -> &NotificationsConfig {
+        &self.notifications
+    }
+
+    pub fn extractor(&self) -> &ExtractorConfig {
+        &self.extractor
+    }
+
+    pub fn transform(&self) -> &TransformConfig {
+        &self.transform
+    }
+
+    pub fn mapping(&self) -> &MappingConfig {
+        &self.mapping
+    }
+
+    pub fn redaction(&self) -> &RedactionConfig {
+        &self.redaction
+    }
+
+    pub fn schemas(&self) -> &SchemasConfig {
+        &self.schemas
+    }
+
+    pub fn dedup(&self) -> &DedupConfig {
+        &self.dedup
+    }
+
+    pub fn reload(&self) -> &ReloadConfig {
+        &self.reload
+    }
+
+    pub fn auth(&self) -> &AuthConfig {
+        &self.auth
+    }
+
+    pub fn server(&self) -> &ServerConfig {
+        &self.server
+    }
+
+    pub fn http(&self) -> &HttpConfig {
+        &self.http
+    }
+
+    /// Checks values that parse but would stop the service from working, such
+    /// as zero workers or zero intervals, which make the scheduler panic.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        let mut positive = |name: &str, value: u64| {
+            if value == 0 {
+                errors.push(format!("{} must be greater than 0", name));
+            }
+        };
+        positive("handler.concurrency", self.handler.concurrency as u64);
+        positive("handler.receivers", self.handler.receivers as u64);
+        positive(
+            "visibility.timeout_seconds",
+            self.visibility.timeout_seconds,
+        );
+        positive(
+            "visibility.heartbeat_interval_seconds",
+            self.visibility.heartbeat_interval_seconds,
+        );
+        positive(
+            "dedup.persist_interval_seconds",
+            self.dedup.persist_interval_seconds,
+        );
+        positive(
+            "reload.poll_interval_seconds",
+            self.reload.poll_interval_seconds,
+        );
+        positive(
+            "server.tls_poll_interval_seconds",
+            self.server.tls_poll_interval_seconds,
+        );
+        // Messages would become visible again between beats and be redelivered
+        // while still being processed.
+        if self.visibility.heartbeat_interval_seconds >= self.visibility.timeout_seconds {
+            errors.push(String::from(
+                "visibility.heartbeat_interval_seconds must be less than visibility.timeout_seconds",
+            ));
+        }
+        errors.extend(self.http.public.validate("http.public"));
+        errors.extend(self.http.read.validate("http.read"));
+        errors.extend(self.http.ingest.validate("http.ingest"));
+        errors.extend(self.http.admin.validate("http.admin"));
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct HandlerConfig {
+    concurrency: usize,
+    receivers: usize,
+}
+
+impl HandlerConfig {
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    pub fn receivers(&self) -> usize {
+        self.receivers
+    }
+}
+
+impl Default for HandlerConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 10,
+            receivers: 1,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct VisibilityConfig {
+    timeout_seconds: u64,
+    heartbeat_interval_seconds: u64,
+    release_delay_seconds: u64,
+}
+
+impl VisibilityConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_seconds)
+    }
+
+    pub fn heartbeat_interval(&self) -> Duration {
+        Duration::from_secs(self.heartbeat_interval_seconds)
+    }
+
+    pub fn release_delay(&self) -> Duration {
+        Duration::from_secs(self.release_delay_seconds)
+    }
+}
+
+impl Default for VisibilityConfig {
+    fn default() -> Self {
+        Self {
+            timeout_seconds: 30,
+            heartbeat_interval_seconds: 10,
+            release_delay_seconds: 0,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    event_types: Vec<EventType>,
+}
+
+impl NotificationsConfig {
+    pub fn event_types(&self) -> &[EventType] {
+        &self.event_types
+    }
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            event_types: vec![EventType::ObjectCreated],
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ExtractorConfig {
+    max_object_size_bytes: u64,
+}
+
+impl ExtractorConfig {
+    pub fn max_object_size_bytes(&self) -> u64 {
+        self.max_object_size_bytes
+    }
+}
+
+impl Default for ExtractorConfig {
+    fn default() -> Self {
+        Self {
+            max_object_size_bytes: 10 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TransformConfig {
+    stringify_values: bool,
+    key_scheme: KeyScheme,
+    collision_policy: CollisionPolicy,
+    reserved_fields: ReservedFields,
+}
+
+impl TransformConfig {
+    pub fn stringify_values(&self) -> bool {
+        self.stringify_values
+    }
+
+    pub fn key_scheme(&self) -> KeyScheme {
+        self.key_scheme
+    }
+
+    pub fn collision_policy(&self) -> CollisionPolicy {
+        self.collision_policy
+    }
+
+    pub fn reserved_fields(&self) -> &ReservedFields {
+        &self.reserved_fields
+    }
+}
+
+/// Field mapping rules applied to flattened records. Rules for a source replace
+/// the default rules rather than adding to them.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MappingConfig {
+    default: Vec<Rule>,
+    sources: HashMap<String, Vec<Rule>>,
+}
+
+impl MappingConfig {
+    pub fn rules_for(&self, source: &str) -> &[Rule] {
+        self.sources.get(source).unwrap_or(&self.default)
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &Rule> {
+        self.default.iter().chain(self.sources.values().flatten())
+    }
+}
+
+/// Redaction of personal answers. Matching rules are recorded in each record
+/// under `audit_field`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RedactionConfig {
+    fields: Vec<FieldRule>,
+    detectors: Vec<DetectorRule>,
+    audit_field: String,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            fields: Vec::new(),
+            detectors: Vec::new(),
+            audit_field: String::from("redactions"),
+        }
+    }
+}
+
+impl RedactionConfig {
+    pub fn fields(&self) -> &[FieldRule] {
+        &self.fields
+    }
+
+    pub fn detectors(&self) -> &[DetectorRule] {
+        &self.detectors
+    }
+
+    pub fn audit_field(&self) -> &str {
+        &self.audit_field
+    }
+
+    /// The audit field, when any rule could add it to a record. Answers with
+    /// its name are then handled as collisions with a reserved field.
+    pub fn reserved_field(&self) -> Option<&str> {
+        if self.fields.is_empty() && self.detectors.is_empty() {
+            None
+        } else {
+            Some(&self.audit_field)
+        }
+    }
+}
+
+/// Directory of `<source>.json` schemas that event requests are validated
+/// against. Nothing is validated when unset.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SchemasConfig {
+    directory: Option<PathBuf>,
+}
+
+impl SchemasConfig {
+    pub fn directory(&self) -> Option<&PathBuf> {
+        self.directory.as_ref()
+    }
+}
+
+/// Events are keyed on their response id unless `key_field` names a field of
+/// the flattened record, as it is before mapping and redaction. The index is
+/// only persisted when `path` is set.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DedupConfig {
+    enabled: bool,
+    key_field: Option<String>,
+    window_seconds: u64,
+    max_entries: usize,
+    path: Option<PathBuf>,
+    persist_interval_seconds: u64,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            key_field: None,
+            window_seconds: 3600,
+            max_entries: 100_000,
+            path: None,
+            persist_interval_seconds: 60,
+        }
+    }
+}
+
+impl DedupConfig {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn key_field(&self) -> Option<&str> {
+        self.key_field.as_deref()
+    }
+
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window_seconds)
+    }
+
+    pub fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+
+    pub fn path(&self) -> Option<&PathBuf> {
+        self.path.as_ref()
+    }
+
+    pub fn persist_interval(&self) -> Duration {
+        Duration::from_secs(self.persist_interval_seconds)
+    }
+}
+
+/// How often the config file is checked for changes. Only the transform,
+/// mapping and redaction sections take effect without a restart.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ReloadConfig {
+    watch: bool,
+    poll_interval_seconds: u64,
+}
+
+impl Default for ReloadConfig {
+    fn default() -> Self {
+        Self {
+            watch: true,
+            poll_interval_seconds: 10,
+        }
+    }
+}
+
+impl ReloadConfig {
+    pub fn watch(&self) -> bool {
+        self.watch
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(self.poll_interval_seconds)
+    }
+}
+
+/// Bearer tokens and JWT keys for the HTTP API. Protected routes reject every
+/// request when neither is configured, unless `disabled` is set.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    tokens: Vec<StaticToken>,
+    jwks: Option<JwksConfig>,
+    disabled: bool,
+}
+
+impl AuthConfig {
+    pub fn disabled(&self) -> bool {
+        self.disabled
+    }
+
+    pub fn tokens(&self) -> &[StaticToken] {
+        &self.tokens
+    }
+
+    pub fn jwks(&self) -> Option<&JwksConfig> {
+        self.jwks.as_ref()
+    }
+}
+
+/// The HTTP listener. With an admin listener configured the admin routes are
+/// served only there, e.g. on localhost or an internal interface.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    bind: SocketAddr,
+    tls: Option<TlsConfig>,
+    tls_poll_interval_seconds: u64,
+    admin: Option<ListenerConfig>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 8080)),
+            tls: None,
+            tls_poll_interval_seconds: 60,
+            admin: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn listener(&self) -> ListenerConfig {
+        ListenerConfig::new(self.bind, self.tls.clone())
+    }
+
+    pub fn admin(&self) -> Option<&ListenerConfig> {
+        self.admin.as_ref()
+    }
+
+    pub fn tls_poll_interval(&self) -> Duration {
+        Duration::from_secs(self.tls_poll_interval_seconds)
+    }
+}
+
+/// Limits for each group of routes, matching the scope each group requires.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    public: LimitsConfig,
+    read: LimitsConfig,
+    ingest: LimitsConfig,
+    admin: LimitsConfig,
+}
+
+impl HttpConfig {
+    pub fn public(&self) -> &LimitsConfig {
+        &self.public
+    }
+
+    pub fn read(&self) -> &LimitsConfig {
+        &self.read
+    }
+
+    pub fn ingest(&self) -> &LimitsConfig {
+        &self.ingest
+    }
+
+    pub fn admin(&self) -> &LimitsConfig {
+        &self.admin
+    }
+}
+
+pub fn path() -> Option<PathBuf> {
+    env::var("CONFIG_FILE").ok().map(PathBuf::from)
+}
+
+pub fn load() -> Config {
+    let config: Config = match path() {
+        Some(path) => {
+            tracing::info!("Loading configuration from '{}'", path.display());
+            let content = fs::read_to_string(&path).expect("Config file should be readable");
+            serde_json::from_str(&content).expect("Config file should be valid")
+        }
+        None => {
+            tracing::info!("No config file provided. Using defaults");
+            Config::default()
+        }
+    };
+    if let Err(errors) = config.validate() {
+        panic!("Config file should be valid: {}", errors.join("; "));
+    }
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialises_partial_config_with_defaults() {
+        let actual: Config = serde_json::from_str(r#"{"handler":{"concurrency":4}}"#).unwrap();
+        assert_eq!(actual.handler().concurrency(), 4);
+        assert_eq!(actual.handler().receivers(), 1);
+    }
+
+    #[test]
+    fn deserialises_admin_listener() {
+        let actual: Config =
+            serde_json::from_str(r#"{"server":{"admin":{"bind":"127.0.0.1:8081"}}}"#).unwrap();
+        assert_eq!(actual.server().bind, SocketAddr::from(([0, 0, 0, 0], 8080)));
+        assert!(actual.server().admin().is_some());
+    }
+
+    #[test]
+    fn rejects_zero_workers_and_intervals() {
+        let actual: Config = serde_json::from_str(
+            r#"{"handler":{"receivers":0},"dedup":{"persist_interval_seconds":0}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            actual.validate(),
+            Err(vec![
+                String::from("handler.receivers must be greater than 0"),
+                String::from("dedup.persist_interval_seconds must be greater than 0"),
+            ])
+        );
+
+        let actual: Config = serde_json::from_str(
+            r#"{"visibility":{"timeout_seconds":0,"heartbeat_interval_seconds":0}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            actual.validate(),
+            Err(vec![
+                String::from("visibility.timeout_seconds must be greater than 0"),
+                String::from("visibility.heartbeat_interval_seconds must be greater than 0"),
+                String::from(
+                    "visibility.heartbeat_interval_seconds must be less than visibility.timeout_seconds"
+                ),
+            ])
+        );
+
+        let actual: Config = serde_json::from_str(
+            r#"{"visibility":{"timeout_seconds":10,"heartbeat_interval_seconds":10}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            actual.validate(),
+            Err(vec![String::from(
+                "visibility.heartbeat_interval_seconds must be less than visibility.timeout_seconds"
+            )])
+        );
+        assert_eq!(Config::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn deserialises_event_types() {
+        let actual: Config =
+            serde_json::from_str(r#"{"notifications":{"event_types":["ObjectRemoved"]}}"#).unwrap();
+        assert_eq!(
+            actual.notifications().event_types(),
+            &[EventType::ObjectRemoved]
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use aws_sdk_sqs::Client;
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{metrics, model::Notification};
+
+/// An error means the rejection was not recorded, so the message should be
+/// released and retried rather than deleted.
+#[async_trait]
+pub trait DeadLetterer {
+    async fn dead_letter(&self, notification: &Notification, reason: &str) -> Result<(), String>;
+}
+
+#[derive(Debug, Serialize)]
+struct DeadLetter<'a> {
+    message_id: &'a str,
+    created: &'a DateTime<Utc>,
+    bucket: &'a str,
+    key: &'a str,
+    version_id: Option<&'a str>,
+    reason: &'a str,
+}
+
+impl<'a> DeadLetter<'a> {
+    fn new(notification: &'a Notification, reason: &'a str) -> Self {
+        Self {
+            message_id: notification.message_id(),
+            created: notification.created(),
+            bucket: notification.bucket(),
+            key: notification.key(),
+            version_id: notification.version_id(),
+            reason,
+        }
+    }
+}
+
+pub struct SqsDeadLetterer {
+    client: Client,
+    queue_url: String,
+}
+
+impl SqsDeadLetterer {
+    pub fn new(client: Client, queue_url: &str) -> Self {
+        Self {
+            client,
+            queue_url: String::from(queue_url),
+        }
+    }
+}
+
+#[async_trait]
+impl DeadLetterer for SqsDeadLetterer {
+    async fn dead_letter(&self, notification: &Notification, reason: &str) -> Result<(), String> {
+        let body = serde_json::to_string(&DeadLetter::new(notification, reason)).unwrap();
+
+        tracing::warn!(
+            "Dead-lettering 's3://{}/{}' to '{}': {}",
+            notification.bucket(),
+            notification.key(),
+            self.queue_url,
+            reason
+        );
+        self.client
+            .send_message()
+            .queue_url(&self.queue_url)
+            .message_body(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        metrics::increment("dead_letters_total", &[]);
+        Ok(())
+    }
+}
+
+/// Used when no dead-letter queue is configured, so rejected objects are at
+/// least visible in the logs.
+pub struct LogDeadLetterer;
+
+#[async_trait]
+impl DeadLetterer for LogDeadLetterer {
+    async fn dead_letter(&self, notification: &Notification, reason: &str) -> Result<(), String> {
+        let body = serde_json::to_string(&DeadLetter::new(notification, reason)).unwrap();
+        tracing::error!("Dropping rejected notification: {}", body);
+        metrics::increment("dead_letters_total", &[]);
+        Ok(())
+    }
+}
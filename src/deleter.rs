@@ -1,9 +1,18 @@
-use aws_sdk_sqs::Client;
+use std::time::Duration;
+
+use aws_sdk_sqs::{
+    types::{BatchResultErrorEntry, DeleteMessageBatchRequestEntry, DeleteMessageBatchResultEntry},
+    Client,
+};
 use axum::async_trait;
 
+const MAX_BATCH_SIZE: usize = 10;
+const MAX_ATTEMPTS: u32 = 3;
+
 #[async_trait]
 pub trait MessageDeleter {
-    async fn delete(&self, receipt_handle: &str);
+    /// Deletes the messages, returning the receipt handles that were deleted.
+    async fn delete(&self, receipt_handles: &[String]) -> Vec<String>;
 }
 
 pub struct SqsMessageDeleter {
@@ -18,17 +27,158 @@ impl SqsMessageDeleter {
             queue_url: String::from(queue_url),
         }
     }
+
+    async fn delete_batch(&self, receipt_handles: &[String]) -> Vec<String> {
+        let mut pending: Vec<&str> = receipt_handles.iter().map(String::as_str).collect();
+        let mut deleted = Vec::new();
+
+        for attempt in 1..=MAX_ATTEMPTS {
+            let result = self
+                .client
+                .delete_message_batch()
+                .queue_url(&self.queue_url)
+                .set_entries(Some(entries_from(&pending)))
+                .send()
+                .await;
+
+            // A failed request, e.g. throttled or timed out, retries the whole chunk.
+            match result {
+                Ok(output) => {
+                    deleted.extend(succeeded(&pending, output.successful()));
+                    pending = retryable(&pending, output.failed());
+                }
+                Err(e) => tracing::warn!(
+                    "Delete request to '{}' failed. Error: {}",
+                    self.queue_url,
+                    e
+                ),
+            }
+            if pending.is_empty() {
+                return deleted;
+            }
+
+            tracing::warn!(
+                "Failed to delete {} messages from '{}' on attempt {}",
+                pending.len(),
+                self.queue_url,
+                attempt
+            );
+            if attempt < MAX_ATTEMPTS {
+                tokio::time::sleep(Duration::from_millis(100 * u64::from(attempt))).await;
+            }
+        }
+
+        tracing::error!(
+            "Giving up deleting {} messages from '{}' after {} attempts",
+            pending.len(),
+            self.queue_url,
+            MAX_ATTEMPTS
+        );
+        deleted
+    }
 }
 
 #[async_trait]
 impl MessageDeleter for SqsMessageDeleter {
-    async fn delete(&self, receipt_handle: &str) {
-        self.client
-            .delete_message()
-            .queue_url(&self.queue_url)
-            .receipt_handle(receipt_handle)
-            .send()
-            .await
-            .unwrap();
+    async fn delete(&self, receipt_handles: &[String]) -> Vec<String> {
+        let mut deleted = Vec::new();
+        for chunk in receipt_handles.chunks(MAX_BATCH_SIZE) {
+            deleted.extend(self.delete_batch(chunk).await);
+        }
+        deleted
+    }
+}
+
+// Entry ids are positions in `receipt_handles` so failures can be mapped back.
+fn entries_from(receipt_handles: &[&str]) -> Vec<DeleteMessageBatchRequestEntry> {
+    receipt_handles
+        .iter()
+        .enumerate()
+        .map(|(idx, receipt_handle)| {
+            DeleteMessageBatchRequestEntry::builder()
+                .id(idx.to_string())
+                .receipt_handle(*receipt_handle)
+                .build()
+                .unwrap()
+        })
+        .collect()
+}
+
+fn succeeded(
+    receipt_handles: &[&str],
+    successful: &[DeleteMessageBatchResultEntry],
+) -> Vec<String> {
+    successful
+        .iter()
+        .filter_map(|entry| {
+            let idx = entry.id().parse::<usize>().ok()?;
+            receipt_handles.get(idx).map(|handle| String::from(*handle))
+        })
+        .collect()
+}
+
+fn retryable<'a>(receipt_handles: &[&'a str], failed: &[BatchResultErrorEntry]) -> Vec<&'a str> {
+    failed
+        .iter()
+        .filter_map(|entry| {
+            let receipt_handle = entry
+                .id()
+                .parse::<usize>()
+                .ok()
+                .and_then(|idx| receipt_handles.get(idx))?;
+
+            if entry.sender_fault() {
+                tracing::error!(
+                    "Not retrying deletion of message with receipt handle '{}': {} {}",
+                    receipt_handle,
+                    entry.code(),
+                    entry.message().unwrap_or_default()
+                );
+                return None;
+            }
+            Some(*receipt_handle)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_only_failed_entries() {
+        let failed = vec![failure("2", false)];
+        let actual = retryable(&["a", "b", "c"], &failed);
+        assert_eq!(actual, vec!["c"])
+    }
+
+    #[test]
+    fn does_not_retry_sender_faults() {
+        let failed = vec![failure("0", true), failure("1", false)];
+        let actual = retryable(&["a", "b", "c"], &failed);
+        assert_eq!(actual, vec!["b"])
+    }
+
+    #[test]
+    fn maps_successful_entries_to_handles() {
+        let successful = vec![success("0"), success("2")];
+        let actual = succeeded(&["a", "b", "c"], &successful);
+        assert_eq!(actual, vec!["a", "c"])
+    }
+
+    fn success(id: &str) -> DeleteMessageBatchResultEntry {
+        DeleteMessageBatchResultEntry::builder()
+            .id(id)
+            .build()
+            .unwrap()
+    }
+
+    fn failure(id: &str, sender_fault: bool) -> BatchResultErrorEntry {
+        BatchResultErrorEntry::builder()
+            .id(id)
+            .code("InternalError")
+            .sender_fault(sender_fault)
+            .build()
+            .unwrap()
     }
 }
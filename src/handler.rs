@@ -1,11 +1,23 @@
 use std::sync::Arc;
 
-use crate::{deleter::MessageDeleter, processor::NotificationProcessor, supplier::Supplier};
+use futures::future::join_all;
+use tokio::sync::Semaphore;
+
+use crate::{
+    activity::{self, Activity},
+    deleter::MessageDeleter,
+    model::{Message, Notification},
+    processor::NotificationProcessor,
+    supplier::Supplier,
+    visibility::Heartbeat,
+};
 
 pub struct EventHandler {
     supplier: Arc<dyn Supplier + Send + Sync>,
     processor: Arc<dyn NotificationProcessor + Send + Sync>,
     deleter: Arc<dyn MessageDeleter + Send + Sync>,
+    heartbeat: Arc<Heartbeat>,
+    permits: Semaphore,
 }
 
 impl EventHandler {
@@ -13,26 +25,272 @@ impl EventHandler {
         supplier: Arc<dyn Supplier + Send + Sync>,
         processor: Arc<dyn NotificationProcessor + Send + Sync>,
         deleter: Arc<dyn MessageDeleter + Send + Sync>,
+        heartbeat: Arc<Heartbeat>,
+        concurrency: usize,
     ) -> Self {
         Self {
             supplier,
             processor,
             deleter,
+            heartbeat,
+            permits: Semaphore::new(concurrency),
         }
     }
 
     pub async fn handle(&self) {
-        let notifications = self.supplier.get().await;
+        let messages = self.supplier.get().await;
 
-        if notifications.is_empty() {
+        if messages.is_empty() {
             return;
         }
 
+        let receipt_handles: Vec<String> = messages
+            .iter()
+            .map(|message| String::from(message.receipt_handle()))
+            .collect();
+        self.heartbeat.track(&receipt_handles).await;
+        for message in &messages {
+            activity::publish(Activity::MessageReceived {
+                message_id: String::from(message.message_id()),
+                notifications: message.notifications().len(),
+            });
+        }
+
+        for message in messages.iter().filter(|m| m.notifications().is_empty()) {
+            tracing::info!(
+                "Message '{}' has nothing to process and will be deleted",
+                message.message_id()
+            );
+        }
+
+        let notifications: Vec<&Notification> =
+            messages.iter().flat_map(Message::notifications).collect();
         tracing::info!("Processing {} notifications", notifications.len());
-        for notification in notifications {
-            self.processor.process(&notification).await;
-            tracing::info!("Deleting message '{}'", notification.message_id());
-            self.deleter.delete(notification.receipt_handle()).await;
+        let failed: Vec<String> = join_all(
+            notifications
+                .into_iter()
+                .map(|notification| self.process(notification)),
+        )
+        .await
+        .into_iter()
+        .flatten()
+        .collect();
+
+        // A message is only deleted once every notification it holds succeeded.
+        let (failed, succeeded): (Vec<String>, Vec<String>) = receipt_handles
+            .into_iter()
+            .partition(|receipt_handle| failed.contains(receipt_handle));
+
+        self.heartbeat.untrack(&succeeded).await;
+        tracing::info!("Deleting {} messages", succeeded.len());
+        let deleted = self.deleter.delete(&succeeded).await;
+        for message in &messages {
+            if deleted
+                .iter()
+                .any(|handle| handle == message.receipt_handle())
+            {
+                activity::publish(Activity::MessageDeleted {
+                    message_id: String::from(message.message_id()),
+                });
+            }
+        }
+
+        self.heartbeat.release(&failed).await;
+    }
+
+    // Permits are shared by every receive loop, so the limit applies across all
+    // in-flight notifications rather than per call to `handle`.
+    async fn process(&self, notification: &Notification) -> Option<String> {
+        let _permit = self.permits.acquire().await.unwrap();
+        match self.processor.process(notification).await {
+            Ok(()) => {
+                tracing::info!(
+                    "Processed notification from message '{}'",
+                    notification.message_id()
+                );
+                None
+            }
+            Err(e) => {
+                tracing::error!(
+                    "Failed to process notification from message '{}': {}",
+                    notification.message_id(),
+                    e
+                );
+                Some(String::from(notification.receipt_handle()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+        time::Duration,
+    };
+
+    use axum::async_trait;
+    use chrono::Utc;
+
+    use crate::{processor::Error, visibility::VisibilityChanger};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn limits_concurrent_processing() {
+        let processor = Arc::new(SlowProcessor::default());
+        let deleter = Arc::new(RecordingDeleter::default());
+        let handler = EventHandler::new(
+            Arc::new(FixedSupplier),
+            processor.clone(),
+            deleter.clone(),
+            heartbeat(Arc::new(RecordingChanger::default())),
+            2,
+        );
+
+        handler.handle().await;
+
+        assert_eq!(processor.max_in_flight.load(Ordering::SeqCst), 2);
+        assert_eq!(deleter.deleted.lock().unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn deletes_message_after_processing() {
+        let processor = Arc::new(SlowProcessor::default());
+        let deleter = Arc::new(RecordingDeleter::default());
+        let handler = EventHandler::new(
+            Arc::new(FixedSupplier),
+            processor.clone(),
+            deleter.clone(),
+            heartbeat(Arc::new(RecordingChanger::default())),
+            5,
+        );
+
+        handler.handle().await;
+
+        let processed = processor.processed.lock().unwrap();
+        for receipt_handle in deleter.deleted.lock().unwrap().iter() {
+            assert!(processed.contains(receipt_handle));
+        }
+    }
+
+    #[tokio::test]
+    async fn releases_failed_messages_instead_of_deleting() {
+        let deleter = Arc::new(RecordingDeleter::default());
+        let changer = Arc::new(RecordingChanger::default());
+        let handler = EventHandler::new(
+            Arc::new(FixedSupplier),
+            Arc::new(FailingProcessor),
+            deleter.clone(),
+            heartbeat(changer.clone()),
+            5,
+        );
+
+        handler.handle().await;
+
+        assert_eq!(*deleter.deleted.lock().unwrap(), vec![String::from("0")]);
+        let released = changer.released.lock().unwrap();
+        assert_eq!(released.len(), 4);
+        assert!(!released.contains(&String::from("0")));
+    }
+
+    struct FixedSupplier;
+
+    #[async_trait]
+    impl Supplier for FixedSupplier {
+        async fn get(&self) -> Vec<Message> {
+            (0..5)
+                .map(|idx| {
+                    let id = idx.to_string();
+                    Message::new(&id, &id, vec![notification(&id)])
+                })
+                .collect()
+        }
+    }
+
+    #[derive(Default)]
+    struct SlowProcessor {
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+        processed: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl NotificationProcessor for SlowProcessor {
+        async fn process(&self, notification: &Notification) -> Result<(), Error> {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            self.processed
+                .lock()
+                .unwrap()
+                .push(String::from(notification.receipt_handle()));
+            Ok(())
+        }
+    }
+
+    struct FailingProcessor;
+
+    #[async_trait]
+    impl NotificationProcessor for FailingProcessor {
+        async fn process(&self, notification: &Notification) -> Result<(), Error> {
+            match notification.receipt_handle() {
+                "0" => Ok(()),
+                _ => Err(Error::Extract(String::from("unavailable"))),
+            }
         }
     }
+
+    #[derive(Default)]
+    struct RecordingDeleter {
+        deleted: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl MessageDeleter for RecordingDeleter {
+        async fn delete(&self, receipt_handles: &[String]) -> Vec<String> {
+            self.deleted
+                .lock()
+                .unwrap()
+                .extend_from_slice(receipt_handles);
+            receipt_handles.to_vec()
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingChanger {
+        released: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl VisibilityChanger for RecordingChanger {
+        async fn change_visibility(&self, receipt_handles: &[String], _timeout: Duration) {
+            self.released
+                .lock()
+                .unwrap()
+                .extend_from_slice(receipt_handles);
+        }
+    }
+
+    fn heartbeat(changer: Arc<RecordingChanger>) -> Arc<Heartbeat> {
+        Arc::new(Heartbeat::new(
+            changer,
+            Duration::from_secs(30),
+            Duration::ZERO,
+        ))
+    }
+
+    fn notification(id: &str) -> Notification {
+        Notification::builder()
+            .message_id(id)
+            .receipt_handle(id)
+            .created(Utc::now())
+            .bucket("test-bucket")
+            .key("1234.json")
+            .build()
+    }
 }
@@ -0,0 +1,284 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{DefaultBodyLimit, Request},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use serde::Deserialize;
+use tokio::sync::Semaphore;
+use tower_http::{
+    compression::CompressionLayer, limit::RequestBodyLimitLayer, timeout::TimeoutLayer,
+};
+
+use crate::metrics;
+
+/// Limits for one group of routes. Requests over a limit get 408, 413 or 429
+/// rather than tying up the service.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    timeout_seconds: u64,
+    max_body_bytes: usize,
+    max_concurrency: Option<usize>,
+    rate_limit: Option<RateLimit>,
+    compress: bool,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            timeout_seconds: 30,
+            max_body_bytes: 1024 * 1024,
+            max_concurrency: Some(64),
+            rate_limit: None,
+            compress: true,
+        }
+    }
+}
+
+impl LimitsConfig {
+    /// Values that would reject every request in the group, or panic when
+    /// working out how long to wait for the rate limit.
+    pub fn validate(&self, group: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.timeout_seconds == 0 {
+            errors.push(format!("{}.timeout_seconds must be greater than 0", group));
+        }
+        if self.max_concurrency == Some(0) {
+            errors.push(format!("{}.max_concurrency must be greater than 0", group));
+        }
+        if let Some(rate) = self.rate_limit {
+            if !(rate.per_second.is_finite() && rate.per_second > 0.0) {
+                errors.push(format!(
+                    "{}.rate_limit.per_second must be greater than 0",
+                    group
+                ));
+            }
+            if rate.burst == 0 {
+                errors.push(format!("{}.rate_limit.burst must be greater than 0", group));
+            }
+        }
+        errors
+    }
+}
+
+/// A token bucket shared by every caller of the group, refilled at
+/// `per_second` up to `burst` requests.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct RateLimit {
+    per_second: f64,
+    burst: u32,
+}
+
+struct Bucket {
+    rate: RateLimit,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl Bucket {
+    fn new(rate: RateLimit, now: Instant) -> Self {
+        Self {
+            rate,
+            state: Mutex::new((f64::from(rate.burst), now)),
+        }
+    }
+
+    /// Takes a token, or says how long until one is available.
+    fn take(&self, now: Instant) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap();
+        let (tokens, updated) = *state;
+        let elapsed = now.saturating_duration_since(updated).as_secs_f64();
+        let tokens = (tokens + elapsed * self.rate.per_second).min(f64::from(self.rate.burst));
+
+        if tokens >= 1.0 {
+            *state = (tokens - 1.0, now);
+            Ok(())
+        } else {
+            *state = (tokens, now);
+            Err(Duration::from_secs_f64(
+                (1.0 - tokens) / self.rate.per_second,
+            ))
+        }
+    }
+}
+
+/// Applies the limits to every route on the router. The rate limit is checked
+/// first so rejected requests cost the least. Apply this before auth so that
+/// callers auth rejects don't use up the group's budget.
+pub fn apply(router: Router, group: &'static str, config: &LimitsConfig) -> Router {
+    let mut router = router;
+    if config.compress {
+        router = router.route_layer(CompressionLayer::new());
+    }
+    router = router
+        .route_layer(TimeoutLayer::with_status_code(
+            StatusCode::REQUEST_TIMEOUT,
+            Duration::from_secs(config.timeout_seconds),
+        ))
+        .route_layer(RequestBodyLimitLayer::new(config.max_body_bytes))
+        .route_layer(DefaultBodyLimit::max(config.max_body_bytes));
+
+    if let Some(max) = config.max_concurrency {
+        let semaphore = Arc::new(Semaphore::new(max));
+        router = router.route_layer(middleware::from_fn(move |request, next| {
+            acquire(semaphore.clone(), group, request, next)
+        }));
+    }
+    if let Some(rate) = config.rate_limit {
+        let bucket = Arc::new(Bucket::new(rate, Instant::now()));
+        router = router.route_layer(middleware::from_fn(move |request, next| {
+            throttle(bucket.clone(), group, request, next)
+        }));
+    }
+    router
+}
+
+/// Middleware rejecting requests while the group is at its concurrency limit.
+async fn acquire(
+    semaphore: Arc<Semaphore>,
+    group: &'static str,
+    request: Request,
+    next: Next,
+) -> Response {
+    match semaphore.try_acquire() {
+        Ok(_permit) => next.run(request).await,
+        Err(_) => reject(group, "concurrency", None),
+    }
+}
+
+/// Middleware rejecting requests once the group's rate limit is used up.
+async fn throttle(
+    bucket: Arc<Bucket>,
+    group: &'static str,
+    request: Request,
+    next: Next,
+) -> Response {
+    match bucket.take(Instant::now()) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => reject(group, "rate", Some(wait)),
+    }
+}
+
+fn reject(group: &str, limit: &str, retry_after: Option<Duration>) -> Response {
+    metrics::increment(
+        "http_limit_rejections_total",
+        &[("group", group), ("limit", limit)],
+    );
+    // Retry-After is in whole seconds, so round up.
+    let retry_after = retry_after.map_or(1, |wait| wait.as_secs_f64().ceil().max(1.0) as u64);
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after.to_string())],
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, routing::post};
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[test]
+    fn rejects_limits_that_block_every_request() {
+        let limits: LimitsConfig = serde_json::from_value(json!({
+            "timeout_seconds": 0,
+            "max_concurrency": 0,
+            "rate_limit": {"per_second": 0.0, "burst": 0},
+        }))
+        .unwrap();
+        assert_eq!(
+            limits.validate("http.read"),
+            vec![
+                String::from("http.read.timeout_seconds must be greater than 0"),
+                String::from("http.read.max_concurrency must be greater than 0"),
+                String::from("http.read.rate_limit.per_second must be greater than 0"),
+                String::from("http.read.rate_limit.burst must be greater than 0"),
+            ]
+        );
+        assert!(LimitsConfig::default().validate("http.read").is_empty());
+    }
+
+    #[tokio::test]
+    async fn times_out_slow_requests() {
+        let app = limited(json!({"timeout_seconds": 1}));
+        let response = app.oneshot(request("/slow", "")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn rejects_oversized_bodies() {
+        let app = limited(json!({"max_body_bytes": 8}));
+
+        let response = app.clone().oneshot(request("/echo", "tiny")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app
+            .oneshot(request("/echo", "far too large"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn rejects_requests_over_the_rate() {
+        let app = limited(json!({"rate_limit": {"per_second": 0.1, "burst": 1}}));
+
+        let response = app.clone().oneshot(request("/echo", "")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app.oneshot(request("/echo", "")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "10");
+    }
+
+    #[tokio::test]
+    async fn rejects_requests_over_the_concurrency() {
+        let app = limited(json!({"max_concurrency": 1}));
+
+        let slow = tokio::spawn(app.clone().oneshot(request("/slow", "")));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let response = app.oneshot(request("/echo", "")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        slow.abort();
+    }
+
+    fn limited(config: serde_json::Value) -> Router {
+        let config: LimitsConfig = serde_json::from_value(config).unwrap();
+        let router = Router::new()
+            .route("/echo", post(|body: String| async move { body }))
+            .route("/slow", post(|| tokio::time::sleep(Duration::from_secs(3))));
+        apply(router, "test", &config)
+    }
+
+    fn request(uri: &str, body: &'static str) -> Request {
+        Request::builder()
+            .method("POST")
+            .uri(uri)
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[test]
+    fn refills_tokens_over_time() {
+        let start = Instant::now();
+        let bucket = Bucket::new(
+            RateLimit {
+                per_second: 2.0,
+                burst: 2,
+            },
+            start,
+        );
+
+        assert_eq!(bucket.take(start), Ok(()));
+        assert_eq!(bucket.take(start), Ok(()));
+        assert_eq!(bucket.take(start), Err(Duration::from_millis(500)));
+        assert_eq!(bucket.take(start + Duration::from_millis(500)), Ok(()));
+    }
+}
@@ -1,24 +1,56 @@
-use std::{env, sync::Arc, time::Duration};
+use std::{convert::Infallible, env, sync::Arc, time::Duration};
 
-use axum::{routing::get, Json, Router};
+use activity::Activity;
+use auth::{Auth, Authenticator, Jwks, Scope, StaticTokens};
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    response::sse::{Event as SseEvent, KeepAlive, Sse},
+    routing::{get, post},
+    Json, Router,
+};
+use axum_server::Handle;
+use chrono::NaiveDate;
+use deadletter::{DeadLetterer, LogDeadLetterer, SqsDeadLetterer};
 use deleter::SqsMessageDeleter;
+use futures::{stream, Stream};
 use handler::EventHandler;
-use processor::NotificationProcessorImpl;
+use limits::LimitsConfig;
+use pause::{Component, Controls, Pausable};
+use processor::{
+    Deduplicator, NotificationProcessorImpl, Pipeline, S3Extractor, Schemas, SharedPipeline,
+};
+use reload::Reloader;
+use serde::Deserialize;
+use server::Server;
 use supplier::SqsSupplier;
 use tokio::{
-    net::TcpListener,
+    sync::broadcast::error::RecvError,
     time::{interval_at, Instant},
 };
-use writer::{BatchWriter, S3Writer};
+use utoipa::IntoParams;
+use visibility::{Heartbeat, SqsVisibilityChanger};
+use writer::{BatchWriter, FlushFilter, S3Writer, Written};
 
+mod activity;
+mod auth;
 mod batch;
+mod config;
+mod deadletter;
 mod deleter;
 mod handler;
+mod limits;
+mod metrics;
 mod model;
+mod openapi;
+mod pause;
 mod processor;
+mod reload;
 mod schedule;
+mod server;
 mod shutdown;
 mod supplier;
+mod visibility;
 mod writer;
 
 #[tokio::main]
@@ -26,10 +58,13 @@ async fn main() {
     let subscriber = tracing_subscriber::FmtSubscriber::default();
     tracing::subscriber::set_global_default(subscriber).unwrap();
 
+    let config = config::load();
+
     let localstack_endpoint = env::var("LOCALSTACK_ENDPOINT").expect("Endpoint should be provided");
     let queue_url = env::var("INPUT_QUEUE_URL").expect("Input queue url should be provided");
     let output_bucket =
         env::var("OUTPUT_BUCKET_NAME").expect("Output bucket name should be provided");
+    let dead_letter_queue_url = env::var("DEAD_LETTER_QUEUE_URL").ok();
 
     let sqs_config = aws_sdk_sqs::Config::builder()
         .endpoint_url(&localstack_endpoint)
@@ -52,55 +87,573 @@ async fn main() {
         .build();
     let s3_client = aws_sdk_s3::Client::from_conf(s3_config);
 
-    let supplier = SqsSupplier::new(sqs_client.clone(), &queue_url);
+    let supplier = SqsSupplier::new(
+        sqs_client.clone(),
+        &queue_url,
+        config.visibility().timeout(),
+        config.notifications().event_types(),
+    );
     let batch_store = Arc::new(batch::StoreImpl::new());
-    let processor =
-        NotificationProcessorImpl::new(Box::new(s3_client.clone()), batch_store.clone());
-    let deleter = SqsMessageDeleter::new(sqs_client, &queue_url);
+    let dead_letterer: Arc<dyn DeadLetterer + Send + Sync> = match dead_letter_queue_url {
+        Some(url) => Arc::new(SqsDeadLetterer::new(sqs_client.clone(), &url)),
+        None => Arc::new(LogDeadLetterer),
+    };
+    let extractor = S3Extractor::new(
+        s3_client.clone(),
+        config.extractor().max_object_size_bytes(),
+    );
+    let redaction_key = env::var("REDACTION_HMAC_KEY").ok().map(String::into_bytes);
+    let pipeline = Arc::new(SharedPipeline::new(
+        Pipeline::new(&config, redaction_key.clone()).expect("Config should be valid"),
+    ));
+    let reloader = Arc::new(Reloader::new(
+        config::path(),
+        redaction_key,
+        pipeline.clone(),
+    ));
+    let schemas = Arc::new(
+        config
+            .schemas()
+            .directory()
+            .map(|directory| Schemas::load(directory))
+            .unwrap_or_default(),
+    );
+    let deduplicator = Arc::new(Deduplicator::new(config.dedup().clone()));
+    let processor = NotificationProcessorImpl::new(
+        Box::new(extractor),
+        batch_store.clone(),
+        dead_letterer,
+        pipeline.clone(),
+        schemas.clone(),
+        deduplicator.clone(),
+    );
+    let deleter = SqsMessageDeleter::new(sqs_client.clone(), &queue_url);
+    let heartbeat = Arc::new(Heartbeat::new(
+        Arc::new(SqsVisibilityChanger::new(sqs_client, &queue_url)),
+        config.visibility().timeout(),
+        config.visibility().release_delay(),
+    ));
 
-    let handler = EventHandler::new(Arc::new(supplier), Arc::new(processor), Arc::new(deleter));
+    let handler = Arc::new(EventHandler::new(
+        Arc::new(supplier),
+        Arc::new(processor),
+        Arc::new(deleter),
+        heartbeat.clone(),
+        config.handler().concurrency(),
+    ));
 
     let writer = S3Writer::new(s3_client, &output_bucket);
     let batch_writer = Arc::new(BatchWriter::new(batch_store.clone(), Box::new(writer)));
 
+    let controls = Arc::new(Controls::default());
+    let consumer = Arc::new(Pausable::new(
+        handler,
+        controls.clone(),
+        Component::Consumer,
+    ));
+
     let (shutdown_send, _) = tokio::sync::broadcast::channel::<()>(1);
-    let handler_task = schedule::task(
-        Arc::new(handler),
-        interval_at(Instant::now(), Duration::from_millis(5_000)),
-        shutdown_send.subscribe(),
-    );
+    let mut background_tasks: Vec<_> = (0..config.handler().receivers())
+        .map(|_| {
+            schedule::task(
+                consumer.clone(),
+                interval_at(Instant::now(), Duration::from_millis(5_000)),
+                shutdown_send.subscribe(),
+            )
+        })
+        .collect();
     let writer_task = schedule::task(
-        batch_writer.clone(),
+        Arc::new(Pausable::new(
+            batch_writer.clone(),
+            controls.clone(),
+            Component::Writer,
+        )),
         interval_at(
             Instant::now() + Duration::from_millis(2_000),
             Duration::from_millis(20_000),
         ),
         shutdown_send.subscribe(),
     );
-    let background_tasks = vec![handler_task, writer_task];
+    let heartbeat_task = schedule::task(
+        heartbeat,
+        interval_at(
+            Instant::now() + config.visibility().heartbeat_interval(),
+            config.visibility().heartbeat_interval(),
+        ),
+        shutdown_send.subscribe(),
+    );
+    let dedup_task = schedule::task(
+        deduplicator.clone(),
+        interval_at(
+            Instant::now() + config.dedup().persist_interval(),
+            config.dedup().persist_interval(),
+        ),
+        shutdown_send.subscribe(),
+    );
+    background_tasks.extend([writer_task, heartbeat_task, dedup_task]);
+    if config.reload().watch() {
+        background_tasks.push(schedule::task(
+            reloader.clone(),
+            interval_at(
+                Instant::now() + config.reload().poll_interval(),
+                config.reload().poll_interval(),
+            ),
+            shutdown_send.subscribe(),
+        ));
+    }
+    let server = Server::new(&config.server().listener()).await;
+    let admin_server = match config.server().admin() {
+        Some(listener) => Some(Server::new(listener).await),
+        None => None,
+    };
+    let certificates = [Some(&server), admin_server.as_ref()]
+        .into_iter()
+        .flatten()
+        .filter_map(Server::certificates);
+    for certificates in certificates {
+        background_tasks.push(schedule::task(
+            certificates,
+            interval_at(
+                Instant::now() + config.server().tls_poll_interval(),
+                config.server().tls_poll_interval(),
+            ),
+            shutdown_send.subscribe(),
+        ));
+    }
+    #[cfg(unix)]
+    tokio::spawn(reload::on_hangup(reloader.clone()));
 
     let summariser = Arc::new(batch::Summariser::new(batch_store));
 
-    let app = Router::new()
+    let mut authenticators: Vec<Arc<dyn Authenticator + Send + Sync>> = Vec::new();
+    if !config.auth().tokens().is_empty() {
+        let tokens = StaticTokens::new(config.auth().tokens().to_vec());
+        authenticators.push(Arc::new(tokens));
+    }
+    if let Some(jwks) = config.auth().jwks() {
+        authenticators.push(Arc::new(Jwks::load(jwks)));
+    }
+    let auth = if config.auth().disabled() {
+        Arc::new(Auth::disabled())
+    } else {
+        Arc::new(Auth::new(authenticators))
+    };
+
+    let public = Router::new()
         .route("/ping", get(ping))
-        .route("/batch/summary", get(move || summary(summariser)));
-
-    let listener = TcpListener::bind("0.0.0.0:8080").await.unwrap();
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown::hook(
-            shutdown_send,
-            batch_writer,
-            background_tasks,
-        ))
-        .await
-        .unwrap();
+        .merge(readiness(controls.clone()))
+        .merge(openapi::router());
+
+    let read = Router::new()
+        .route("/metrics", get(metrics))
+        .route("/events/stream", get(|Query(filter)| stream(filter)))
+        .route("/collisions", get(collisions))
+        .route("/schemas", get(move || list_schemas(schemas)))
+        .route("/batch/summary", {
+            let summariser = summariser.clone();
+            get(move |Query(query)| summary(query, summariser))
+        })
+        .route(
+            "/batch/:source/:date",
+            get(move |Path((source, date)), Query(page)| inspect(source, date, page, summariser)),
+        );
+
+    let ingest = Router::new().route(
+        "/mapping/dry-run",
+        post(move |Json(event)| dry_run(event, pipeline)),
+    );
+
+    let admin = pausing(controls)
+        .route("/batch/flush", {
+            let batch_writer = batch_writer.clone();
+            post(move |Query(filter)| flush(filter, batch_writer))
+        })
+        .route("/admin/config", {
+            let reloader = reloader.clone();
+            get(move || config_view(reloader))
+        })
+        .route("/admin/reload", post(move || reload(reloader)));
+
+    let http = config.http();
+    let read = protected(read, "read", http.read(), auth.clone(), Scope::Read);
+    let ingest = protected(ingest, "ingest", http.ingest(), auth.clone(), Scope::Ingest);
+    let admin = protected(admin, "admin", http.admin(), auth, Scope::Admin);
+    let app = limits::apply(public, "public", http.public())
+        .merge(read)
+        .merge(ingest);
+
+    let handle = Handle::new();
+    let shutdown = shutdown::hook(shutdown_send, batch_writer, deduplicator, background_tasks);
+    tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            shutdown.await;
+            handle.graceful_shutdown(Some(server::SHUTDOWN_GRACE));
+        }
+    });
+
+    match admin_server {
+        Some(admin_server) => {
+            tokio::join!(
+                server.serve(app, handle.clone()),
+                admin_server.serve(admin, handle),
+            );
+        }
+        None => server.serve(app.merge(admin), handle).await,
+    }
 }
 
+#[utoipa::path(get, path = "/ping", tag = "health", responses((status = 200, body = String)))]
 async fn ping() -> &'static str {
     "pong"
 }
 
-async fn summary(summariser: Arc<batch::Summariser>) -> Json<Vec<batch::Summary>> {
-    let summaries = summariser.summary();
-    Json(summaries)
+/// Checks auth before the group's limits, so rejected callers can't use up
+/// the rate and concurrency budgets of the callers allowed in.
+fn protected(
+    router: Router,
+    group: &'static str,
+    limits: &LimitsConfig,
+    auth: Arc<Auth>,
+    scope: Scope,
+) -> Router {
+    auth::protect(limits::apply(router, group, limits), auth, scope)
+}
+
+fn readiness(controls: Arc<Controls>) -> Router {
+    Router::new().route("/ready", get(move || ready(controls)))
+}
+
+fn pausing(controls: Arc<Controls>) -> Router {
+    Router::new()
+        .route("/admin/pause/:component", {
+            let controls = controls.clone();
+            post(move |Path(component)| pause(component, controls))
+        })
+        .route(
+            "/admin/resume/:component",
+            post(move |Path(component)| resume(component, controls)),
+        )
+}
+
+/// Stays 200 while paused and reports the pause in the body. Failing the
+/// probe would take the instance out of its load balancer, and with it the
+/// admin route that resumes it.
+#[utoipa::path(
+    get,
+    path = "/ready",
+    tag = "health",
+    responses((status = 200, description = "Whether each component is running", body = pause::State))
+)]
+async fn ready(controls: Arc<Controls>) -> Json<pause::State> {
+    Json(controls.state())
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/pause/{component}",
+    tag = "admin",
+    params(("component" = Component, Path)),
+    responses((status = 200, body = pause::State)),
+    security(("bearer" = ["admin"]))
+)]
+async fn pause(component: Component, controls: Arc<Controls>) -> Json<pause::State> {
+    controls.pause(component);
+    Json(controls.state())
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/resume/{component}",
+    tag = "admin",
+    params(("component" = Component, Path)),
+    responses((status = 200, body = pause::State)),
+    security(("bearer" = ["admin"]))
+)]
+async fn resume(component: Component, controls: Arc<Controls>) -> Json<pause::State> {
+    controls.resume(component);
+    Json(controls.state())
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct StreamFilter {
+    source: Option<String>,
+}
+
+/// Sends each activity as JSON. Missed activity is reported by a `lagged`
+/// event whose data is the number of events skipped.
+#[utoipa::path(
+    get,
+    path = "/events/stream",
+    tag = "activity",
+    params(StreamFilter),
+    responses((status = 200, content_type = "text/event-stream", body = Activity)),
+    security(("bearer" = ["read"]))
+)]
+async fn stream(filter: StreamFilter) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>>> {
+    let receiver = activity::subscribe();
+    let events = stream::unfold(receiver, move |mut receiver| {
+        let source = filter.source.clone();
+        async move {
+            loop {
+                let event = match receiver.recv().await {
+                    Ok(activity) if activity.matches(source.as_deref()) => {
+                        SseEvent::default().json_data(&activity).unwrap()
+                    }
+                    Ok(_) => continue,
+                    // The subscriber fell behind and missed some activity.
+                    Err(RecvError::Lagged(missed)) => {
+                        SseEvent::default().event("lagged").data(missed.to_string())
+                    }
+                    Err(RecvError::Closed) => return None,
+                };
+                return Some((Ok(event), receiver));
+            }
+        }
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "activity",
+    responses((status = 200, content_type = "text/plain", body = String)),
+    security(("bearer" = ["read"]))
+)]
+async fn metrics() -> String {
+    metrics::render()
+}
+
+#[utoipa::path(
+    get,
+    path = "/collisions",
+    tag = "activity",
+    responses((status = 200, body = Vec<processor::CollisionCount>)),
+    security(("bearer" = ["read"]))
+)]
+async fn collisions() -> Json<Vec<processor::CollisionCount>> {
+    Json(processor::collision_counts())
+}
+
+#[utoipa::path(
+    get,
+    path = "/schemas",
+    tag = "mapping",
+    responses((status = 200, body = Vec<processor::SchemaInfo>)),
+    security(("bearer" = ["read"]))
+)]
+async fn list_schemas(schemas: Arc<Schemas>) -> Json<Vec<processor::SchemaInfo>> {
+    Json(schemas.list())
+}
+
+#[utoipa::path(
+    get,
+    path = "/batch/summary",
+    tag = "batch",
+    params(batch::SummaryQuery),
+    responses((
+        status = 200,
+        description = "A bare array without query parameters, otherwise a page with totals",
+        body = batch::SummaryResponse
+    )),
+    security(("bearer" = ["read"]))
+)]
+async fn summary(
+    query: batch::SummaryQuery,
+    summariser: Arc<batch::Summariser>,
+) -> Json<batch::SummaryResponse> {
+    if query.is_empty() {
+        return Json(batch::SummaryResponse::Summaries(summariser.summary()));
+    }
+    Json(batch::SummaryResponse::Page(summariser.query(&query)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/batch/{source}/{date}",
+    tag = "batch",
+    params(
+        ("source" = String, Path),
+        ("date" = NaiveDate, Path, format = Date),
+        batch::Page
+    ),
+    responses(
+        (status = 200, body = batch::Inspection),
+        (status = 404, description = "No batch is buffered for the partition")
+    ),
+    security(("bearer" = ["read"]))
+)]
+async fn inspect(
+    source: String,
+    date: NaiveDate,
+    page: batch::Page,
+    summariser: Arc<batch::Summariser>,
+) -> Result<Json<batch::Inspection>, StatusCode> {
+    summariser
+        .inspect(&batch::Partition::new(&source, date), &page)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+#[utoipa::path(
+    post,
+    path = "/batch/flush",
+    tag = "batch",
+    params(FlushFilter),
+    responses(
+        (status = 200, description = "The batches written", body = Vec<Written>),
+        (status = 502, description = "Some batches failed to write and were kept", body = Vec<Written>),
+        (status = 408, description = "The flush is still running and will finish in the background")
+    ),
+    security(("bearer" = ["admin"]))
+)]
+async fn flush(
+    filter: FlushFilter,
+    batch_writer: Arc<BatchWriter>,
+) -> (StatusCode, Json<Vec<Written>>) {
+    // Spawned so a request timeout or a dropped connection can't stop a flush
+    // between writing a batch and deleting its records, which would write the
+    // records again on the next flush.
+    let task = tokio::spawn(async move { batch_writer.flush_matching(&filter).await });
+    let written = match task.await {
+        Ok(written) => written,
+        Err(e) => {
+            tracing::error!("Failed to flush batches. Error: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(Vec::new()));
+        }
+    };
+    let status = if written.iter().all(Written::is_ok) {
+        StatusCode::OK
+    } else {
+        StatusCode::BAD_GATEWAY
+    };
+    (status, Json(written))
+}
+
+#[utoipa::path(
+    post,
+    path = "/mapping/dry-run",
+    tag = "mapping",
+    request_body = model::Event,
+    responses(
+        (status = 200, body = processor::DryRun),
+        (status = 422, description = "The event was rejected", body = String)
+    ),
+    security(("bearer" = ["ingest"]))
+)]
+async fn dry_run(
+    event: model::Event,
+    pipeline: Arc<SharedPipeline>,
+) -> Result<Json<processor::DryRun>, (StatusCode, String)> {
+    processor::dry_run(&event, &pipeline.current())
+        .map(Json)
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/config",
+    tag = "admin",
+    responses((status = 200, body = reload::ConfigView)),
+    security(("bearer" = ["admin"]))
+)]
+async fn config_view(reloader: Arc<Reloader>) -> Json<reload::ConfigView> {
+    Json(reloader.view())
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/reload",
+    tag = "admin",
+    responses(
+        (status = 200, body = reload::ConfigView),
+        (status = 422, description = "The config file is invalid and was not applied", body = String)
+    ),
+    security(("bearer" = ["admin"]))
+)]
+async fn reload(reloader: Arc<Reloader>) -> Result<Json<reload::ConfigView>, (StatusCode, String)> {
+    reloader
+        .reload()
+        .map(Json)
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{self, Body},
+        http::{header, Method, Request},
+    };
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_paused_components_while_staying_ready() {
+        let controls = Arc::new(Controls::default());
+        let app = readiness(controls.clone()).merge(pausing(controls));
+
+        let (status, body) = call(&app, Method::POST, "/admin/pause/consumer").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({"consumer": "paused", "writer": "running"}));
+        let (status, body) = call(&app, Method::GET, "/ready").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({"consumer": "paused", "writer": "running"}));
+
+        call(&app, Method::POST, "/admin/resume/consumer").await;
+        let (status, body) = call(&app, Method::GET, "/ready").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({"consumer": "running", "writer": "running"}));
+
+        let (status, _) = call(&app, Method::POST, "/admin/pause/nothing").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn rejected_callers_do_not_use_up_the_rate_limit() {
+        let limits: LimitsConfig =
+            serde_json::from_value(json!({"rate_limit": {"per_second": 0.1, "burst": 1}})).unwrap();
+        let tokens =
+            serde_json::from_value(json!([{"token": "reader", "scopes": ["read"]}])).unwrap();
+        let auth = Arc::new(Auth::new(vec![Arc::new(StaticTokens::new(tokens))]));
+        let app = protected(
+            Router::new().route("/ping", get(ping)),
+            "read",
+            &limits,
+            auth,
+            Scope::Read,
+        );
+
+        for _ in 0..3 {
+            let (status, _) = call(&app, Method::GET, "/ping").await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+        let request = Request::builder()
+            .uri("/ping")
+            .header(header::AUTHORIZATION, "Bearer reader")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    async fn call(app: &Router, method: Method, uri: &str) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
 }
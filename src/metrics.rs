@@ -0,0 +1,79 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{LazyLock, Mutex},
+};
+
+pub type Labels = Vec<(String, String)>;
+
+static COUNTERS: LazyLock<Mutex<BTreeMap<String, BTreeMap<Labels, u64>>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+pub fn increment(name: &str, labels: &[(&str, &str)]) {
+    add(name, labels, 1);
+}
+
+pub fn add(name: &str, labels: &[(&str, &str)], value: u64) {
+    let labels = labels
+        .iter()
+        .map(|(key, value)| (String::from(*key), String::from(*value)))
+        .collect();
+
+    *COUNTERS
+        .lock()
+        .unwrap()
+        .entry(String::from(name))
+        .or_default()
+        .entry(labels)
+        .or_default() += value;
+}
+
+/// Renders every counter in the Prometheus text exposition format.
+pub fn render() -> String {
+    let counters = COUNTERS.lock().unwrap();
+    let mut output = String::new();
+
+    for (name, series) in counters.iter() {
+        writeln!(output, "# TYPE {} counter", name).unwrap();
+        for (labels, value) in series {
+            writeln!(output, "{}{} {}", name, render_labels(labels), value).unwrap();
+        }
+    }
+    output
+}
+
+fn render_labels(labels: &Labels) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+
+    let rendered: Vec<String> = labels
+        .iter()
+        .map(|(key, value)| format!("{}=\"{}\"", key, escape(value)))
+        .collect();
+    format!("{{{}}}", rendered.join(","))
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_counters() {
+        increment("metrics_test_total", &[("source", "somewhere")]);
+        add("metrics_test_total", &[("source", "somewhere")], 2);
+        increment("metrics_test_total", &[("source", "\"quoted\"")]);
+
+        let actual = render();
+        assert!(actual.contains("# TYPE metrics_test_total counter\n"));
+        assert!(actual.contains("metrics_test_total{source=\"somewhere\"} 3\n"));
+        assert!(actual.contains("metrics_test_total{source=\"\\\"quoted\\\"\"} 1\n"));
+    }
+}
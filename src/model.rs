@@ -1,13 +1,25 @@
+mod envelope;
 mod event;
+mod event_bridge;
+mod event_type;
+mod message;
 mod notification;
 mod s3_notification;
+mod sns_notification;
+mod test_event;
 
-pub use event::Answer;
+pub use envelope::Envelope;
 pub use event::Event;
+pub use event::Request;
+pub use event_bridge::EventBridgeNotification;
+pub use event_type::EventType;
+pub use message::Message;
 pub use notification::Notification;
 
 pub use s3_notification::Record;
 pub use s3_notification::S3Notification;
+pub use sns_notification::SnsNotification;
+pub use test_event::TestEvent;
 
 #[cfg(test)]
-pub use event::{Request, Response};
+pub use event::{Answer, Response};
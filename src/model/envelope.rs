@@ -0,0 +1,56 @@
+use serde::Deserialize;
+
+use super::{EventBridgeNotification, S3Notification, SnsNotification, TestEvent};
+
+/// The formats an S3 notification can arrive on the queue in.
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Envelope {
+    S3(S3Notification),
+    Test(TestEvent),
+    Sns(SnsNotification),
+    EventBridge(EventBridgeNotification),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_s3_notification() {
+        let actual: Envelope = serde_json::from_str(r#"{"Records":[]}"#).unwrap();
+        assert!(matches!(actual, Envelope::S3(_)))
+    }
+
+    #[test]
+    fn detects_test_event() {
+        let actual: Envelope = serde_json::from_str(
+            r#"{"Service":"Amazon S3","Event":"s3:TestEvent","Time":"2024-08-10T12:53:00.000Z","Bucket":"test-bucket"}"#,
+        )
+        .unwrap();
+        assert!(matches!(actual, Envelope::Test(_)))
+    }
+
+    #[test]
+    fn detects_sns_notification() {
+        let actual: Envelope = serde_json::from_str(
+            r#"{"Type":"Notification","TopicArn":"arn:aws:sns:us-east-1:000000000000:test-topic","Message":"{\"Records\":[]}"}"#,
+        )
+        .unwrap();
+        assert!(matches!(actual, Envelope::Sns(_)))
+    }
+
+    #[test]
+    fn detects_event_bridge_notification() {
+        let actual: Envelope = serde_json::from_str(
+            r#"{"detail-type":"Object Created","source":"aws.s3","time":"2024-08-10T12:53:00Z","detail":{"bucket":{"name":"test-bucket"},"object":{"key":"1234.json"}}}"#,
+        )
+        .unwrap();
+        assert!(matches!(actual, Envelope::EventBridge(_)))
+    }
+
+    #[test]
+    fn does_not_deserialise_unknown_format() {
+        assert!(serde_json::from_str::<Envelope>(r#"{"Message":1}"#).is_err())
+    }
+}
@@ -1,8 +1,10 @@
 use std::collections::HashMap;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
 
-#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Deserialize, ToSchema)]
 pub struct Event {
     request: Request,
     response: Response,
@@ -23,7 +25,7 @@ impl Event {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
 pub struct Request {
     source: String,
     answers: HashMap<String, Answer>,
@@ -47,14 +49,23 @@ impl Request {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize)]
-#[serde(untagged)]
-pub enum Answer {
-    Simple(String),
-    Collection(Vec<HashMap<String, String>>),
+/// An answer can be any JSON value, so types are kept through to the output.
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(transparent)]
+pub struct Answer(Value);
+
+impl Answer {
+    #[cfg(test)]
+    pub fn new(value: Value) -> Self {
+        Self(value)
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.0
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Deserialize, ToSchema)]
 pub struct Response {
     id: String,
 }
@@ -74,6 +85,8 @@ impl Response {
 
 #[cfg(test)]
 mod tests {
+    use serde_json::json;
+
     use super::*;
 
     #[test]
@@ -89,16 +102,13 @@ mod tests {
 
     fn expected() -> Event {
         let mut answers = HashMap::new();
-        answers.insert(
-            String::from("first_name"),
-            Answer::Simple(String::from("Tim")),
-        );
+        answers.insert(String::from("first_name"), Answer(json!("Tim")));
+        answers.insert(String::from("age"), Answer(json!(42)));
+        answers.insert(String::from("subscribed"), Answer(json!(true)));
+        answers.insert(String::from("nickname"), Answer(json!(null)));
         answers.insert(
             String::from("pets"),
-            Answer::Collection(vec![HashMap::from([
-                (String::from("type"), String::from("Cat")),
-                (String::from("name"), String::from("Tiffin")),
-            ])]),
+            Answer(json!([{"type": "Cat", "name": "Tiffin", "age": 3}])),
         );
         Event {
             request: Request {
@@ -112,6 +122,6 @@ mod tests {
     }
 
     fn event() -> &'static str {
-        r#"{"request":{"source":"somewhere","answers":{"first_name":"Tim","pets":[{"type":"Cat","name":"Tiffin"}]}}, "response":{"id":"1234"}}"#
+        r#"{"request":{"source":"somewhere","answers":{"first_name":"Tim","age":42,"subscribed":true,"nickname":null,"pets":[{"type":"Cat","name":"Tiffin","age":3}]}}, "response":{"id":"1234"}}"#
     }
 }
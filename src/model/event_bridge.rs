@@ -0,0 +1,120 @@
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct EventBridgeNotification {
+    #[serde(rename(deserialize = "detail-type"))]
+    detail_type: String,
+    time: DateTime<Utc>,
+    detail: Detail,
+}
+
+impl EventBridgeNotification {
+    pub fn detail_type(&self) -> &str {
+        &self.detail_type
+    }
+
+    pub fn time(&self) -> &DateTime<Utc> {
+        &self.time
+    }
+
+    pub fn detail(&self) -> &Detail {
+        &self.detail
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct Detail {
+    bucket: Bucket,
+    object: Object,
+}
+
+impl Detail {
+    pub fn bucket(&self) -> &Bucket {
+        &self.bucket
+    }
+
+    pub fn object(&self) -> &Object {
+        &self.object
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct Bucket {
+    name: String,
+}
+
+impl Bucket {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct Object {
+    key: String,
+    size: Option<i64>,
+    etag: Option<String>,
+    #[serde(rename(deserialize = "version-id"))]
+    version_id: Option<String>,
+    sequencer: Option<String>,
+}
+
+impl Object {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn size(&self) -> Option<i64> {
+        self.size
+    }
+
+    pub fn e_tag(&self) -> Option<&str> {
+        self.etag.as_deref()
+    }
+
+    pub fn version_id(&self) -> Option<&str> {
+        self.version_id.as_deref()
+    }
+
+    pub fn sequencer(&self) -> Option<&str> {
+        self.sequencer.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialises_event_bridge_notification() {
+        let actual: EventBridgeNotification = serde_json::from_str(notification()).unwrap();
+        assert_eq!(actual, expected())
+    }
+
+    fn expected() -> EventBridgeNotification {
+        EventBridgeNotification {
+            detail_type: String::from("Object Created"),
+            time: DateTime::parse_from_rfc3339("2024-08-10T12:53:00+00:00")
+                .unwrap()
+                .to_utc(),
+            detail: Detail {
+                bucket: Bucket {
+                    name: String::from("test-bucket"),
+                },
+                object: Object {
+                    key: String::from("1234.json"),
+                    size: Some(5),
+                    etag: Some(String::from("b1946ac92492d2347c6235b4d2611184")),
+                    version_id: None,
+                    sequencer: Some(String::from("00617F08299329D189")),
+                },
+            },
+        }
+    }
+
+    fn notification() -> &'static str {
+        r#"{"version":"0","id":"abcd","detail-type":"Object Created","source":"aws.s3","account":"000000000000","time":"2024-08-10T12:53:00Z","region":"us-east-1","resources":["arn:aws:s3:::test-bucket"],"detail":{"version":"0","bucket":{"name":"test-bucket"},"object":{"key":"1234.json","size":5,"etag":"b1946ac92492d2347c6235b4d2611184","sequencer":"00617F08299329D189"},"request-id":"N4N7GDK58NMKJ12R","requester":"123456789012","reason":"PutObject"}}"#
+    }
+}
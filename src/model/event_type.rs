@@ -0,0 +1,103 @@
+use std::fmt::{self, Display};
+
+use serde::Deserialize;
+
+/// The category of an S3 event, e.g. `ObjectCreated` for `ObjectCreated:Put`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(from = "String")]
+pub enum EventType {
+    ObjectCreated,
+    ObjectRemoved,
+    ObjectRestore,
+    ObjectTagging,
+    ObjectAcl,
+    Replication,
+    LifecycleExpiration,
+    LifecycleTransition,
+    IntelligentTiering,
+    ReducedRedundancyLostObject,
+    Other(String),
+}
+
+impl EventType {
+    /// Maps an EventBridge `detail-type` such as `Object Created` to its type.
+    pub fn from_detail_type(detail_type: &str) -> Self {
+        match detail_type {
+            "Object Created" => EventType::ObjectCreated,
+            "Object Deleted" => EventType::ObjectRemoved,
+            "Object Restore Initiated" | "Object Restore Completed" | "Object Restore Expired" => {
+                EventType::ObjectRestore
+            }
+            "Object Tags Added" | "Object Tags Deleted" => EventType::ObjectTagging,
+            "Object ACL Updated" => EventType::ObjectAcl,
+            "Object Storage Class Changed" => EventType::LifecycleTransition,
+            "Object Access Tier Changed" => EventType::IntelligentTiering,
+            other => EventType::Other(String::from(other)),
+        }
+    }
+}
+
+impl From<String> for EventType {
+    fn from(value: String) -> Self {
+        let name = value
+            .strip_prefix("s3:")
+            .or_else(|| value.strip_prefix("S3:"))
+            .unwrap_or(&value);
+        let category = name.split(':').next().unwrap_or_default();
+
+        match category {
+            "ObjectCreated" => EventType::ObjectCreated,
+            "ObjectRemoved" => EventType::ObjectRemoved,
+            "ObjectRestore" => EventType::ObjectRestore,
+            "ObjectTagging" => EventType::ObjectTagging,
+            "ObjectAcl" => EventType::ObjectAcl,
+            "Replication" => EventType::Replication,
+            "LifecycleExpiration" => EventType::LifecycleExpiration,
+            "LifecycleTransition" => EventType::LifecycleTransition,
+            "IntelligentTiering" => EventType::IntelligentTiering,
+            "ReducedRedundancyLostObject" => EventType::ReducedRedundancyLostObject,
+            _ => EventType::Other(value),
+        }
+    }
+}
+
+impl Display for EventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventType::Other(name) => write!(f, "{}", name),
+            known => write!(f, "{:?}", known),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_event_name() {
+        let actual = EventType::from(String::from("ObjectRemoved:DeleteMarkerCreated"));
+        assert_eq!(actual, EventType::ObjectRemoved)
+    }
+
+    #[test]
+    fn parses_prefixed_event_name() {
+        let actual = EventType::from(String::from("s3:ObjectCreated:Put"));
+        assert_eq!(actual, EventType::ObjectCreated)
+    }
+
+    #[test]
+    fn keeps_unknown_event_name() {
+        let actual = EventType::from(String::from("ObjectExploded:Boom"));
+        assert_eq!(
+            actual,
+            EventType::Other(String::from("ObjectExploded:Boom"))
+        )
+    }
+
+    #[test]
+    fn maps_event_bridge_detail_type() {
+        let actual = EventType::from_detail_type("Object Deleted");
+        assert_eq!(actual, EventType::ObjectRemoved)
+    }
+}
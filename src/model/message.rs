@@ -0,0 +1,31 @@
+use super::Notification;
+
+/// A message received from the queue and the notifications it carried.
+#[derive(Debug)]
+pub struct Message {
+    message_id: String,
+    receipt_handle: String,
+    notifications: Vec<Notification>,
+}
+
+impl Message {
+    pub fn new(message_id: &str, receipt_handle: &str, notifications: Vec<Notification>) -> Self {
+        Self {
+            message_id: String::from(message_id),
+            receipt_handle: String::from(receipt_handle),
+            notifications,
+        }
+    }
+
+    pub fn message_id(&self) -> &str {
+        &self.message_id
+    }
+
+    pub fn receipt_handle(&self) -> &str {
+        &self.receipt_handle
+    }
+
+    pub fn notifications(&self) -> &[Notification] {
+        &self.notifications
+    }
+}
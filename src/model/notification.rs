@@ -8,6 +8,10 @@ pub struct Notification {
     created: DateTime<Utc>,
     bucket: String,
     key: String,
+    size: Option<i64>,
+    e_tag: Option<String>,
+    version_id: Option<String>,
+    sequencer: Option<String>,
 }
 
 impl Notification {
@@ -34,6 +38,22 @@ impl Notification {
     pub fn key(&self) -> &str {
         &self.key
     }
+
+    pub fn size(&self) -> Option<i64> {
+        self.size
+    }
+
+    pub fn e_tag(&self) -> Option<&str> {
+        self.e_tag.as_deref()
+    }
+
+    pub fn version_id(&self) -> Option<&str> {
+        self.version_id.as_deref()
+    }
+
+    pub fn sequencer(&self) -> Option<&str> {
+        self.sequencer.as_deref()
+    }
 }
 
 pub struct Builder {
@@ -42,6 +62,10 @@ pub struct Builder {
     created: Option<DateTime<Utc>>,
     bucket: Option<String>,
     key: Option<String>,
+    size: Option<i64>,
+    e_tag: Option<String>,
+    version_id: Option<String>,
+    sequencer: Option<String>,
 }
 
 impl Builder {
@@ -52,6 +76,10 @@ impl Builder {
             created: None,
             bucket: None,
             key: None,
+            size: None,
+            e_tag: None,
+            version_id: None,
+            sequencer: None,
         }
     }
 
@@ -80,6 +108,26 @@ impl Builder {
         self
     }
 
+    pub fn size(mut self, size: Option<i64>) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn e_tag(mut self, e_tag: Option<&str>) -> Self {
+        self.e_tag = e_tag.map(String::from);
+        self
+    }
+
+    pub fn version_id(mut self, version_id: Option<&str>) -> Self {
+        self.version_id = version_id.map(String::from);
+        self
+    }
+
+    pub fn sequencer(mut self, sequencer: Option<&str>) -> Self {
+        self.sequencer = sequencer.map(String::from);
+        self
+    }
+
     pub fn build(self) -> Notification {
         Notification {
             message_id: self.message_id.unwrap(),
@@ -87,6 +135,10 @@ impl Builder {
             created: self.created.unwrap(),
             bucket: self.bucket.unwrap(),
             key: self.key.unwrap(),
+            size: self.size,
+            e_tag: self.e_tag,
+            version_id: self.version_id,
+            sequencer: self.sequencer,
         }
     }
 }
@@ -1,6 +1,9 @@
 use chrono::DateTime;
 use chrono::Utc;
-use serde::Deserialize;
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Deserializer};
+
+use super::EventType;
 
 #[derive(Debug, PartialEq, Eq, Deserialize)]
 pub struct S3Notification {
@@ -18,10 +21,16 @@ impl S3Notification {
 pub struct Record {
     #[serde(rename(deserialize = "eventTime"))]
     event_time: DateTime<Utc>,
+    #[serde(rename(deserialize = "eventName"))]
+    event_type: EventType,
     s3: S3,
 }
 
 impl Record {
+    pub fn event_type(&self) -> &EventType {
+        &self.event_type
+    }
+
     pub fn event_time(&self) -> &DateTime<Utc> {
         &self.event_time
     }
@@ -60,13 +69,47 @@ impl Bucket {
 
 #[derive(Debug, PartialEq, Eq, Deserialize)]
 pub struct Object {
+    #[serde(deserialize_with = "decode_key")]
     key: String,
+    size: Option<i64>,
+    #[serde(rename(deserialize = "eTag"))]
+    e_tag: Option<String>,
+    #[serde(rename(deserialize = "versionId"))]
+    version_id: Option<String>,
+    sequencer: Option<String>,
 }
 
 impl Object {
     pub fn key(&self) -> &str {
         &self.key
     }
+
+    pub fn size(&self) -> Option<i64> {
+        self.size
+    }
+
+    pub fn e_tag(&self) -> Option<&str> {
+        self.e_tag.as_deref()
+    }
+
+    pub fn version_id(&self) -> Option<&str> {
+        self.version_id.as_deref()
+    }
+
+    pub fn sequencer(&self) -> Option<&str> {
+        self.sequencer.as_deref()
+    }
+}
+
+// S3 form-encodes keys in notifications, so spaces arrive as `+`.
+fn decode_key<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let key = String::deserialize(deserializer)?;
+    Ok(percent_decode_str(&key.replace('+', " "))
+        .decode_utf8_lossy()
+        .into_owned())
 }
 
 #[cfg(test)]
@@ -79,17 +122,30 @@ mod tests {
         assert_eq!(actual, expected())
     }
 
+    #[test]
+    fn deserialises_object_without_optional_fields() {
+        let actual: Object = serde_json::from_str(r#"{"key":"1234.json"}"#).unwrap();
+        assert_eq!(actual.key(), "1234.json");
+        assert_eq!(actual.size(), None);
+        assert_eq!(actual.version_id(), None);
+    }
+
     fn expected() -> S3Notification {
         let record = Record {
             event_time: DateTime::parse_from_rfc3339("2024-08-10T12:53:00.000+00:00")
                 .unwrap()
                 .to_utc(),
+            event_type: EventType::ObjectCreated,
             s3: S3 {
                 bucket: Bucket {
                     name: String::from("test-bucket"),
                 },
                 object: Object {
-                    key: String::from("1234.json"),
+                    key: String::from("input/my file+1.json"),
+                    size: Some(1024),
+                    e_tag: Some(String::from("d41d8cd98f00b204e9800998ecf8427e")),
+                    version_id: Some(String::from("096fKKXTRTtl3on89fVO.nfljtsv6qko")),
+                    sequencer: Some(String::from("0055AED6DCD90281E5")),
                 },
             },
         };
@@ -99,6 +155,6 @@ mod tests {
     }
 
     fn notification() -> &'static str {
-        r#"{"Records":[{"eventTime":"2024-08-10T12:53:00.000Z","eventName":"S3:ObjectCreated","s3":{"bucket":{"name":"test-bucket"},"object":{"key":"1234.json"}}}]}"#
+        r#"{"Records":[{"eventTime":"2024-08-10T12:53:00.000Z","eventName":"S3:ObjectCreated","s3":{"bucket":{"name":"test-bucket"},"object":{"key":"input/my+file%2B1.json","size":1024,"eTag":"d41d8cd98f00b204e9800998ecf8427e","versionId":"096fKKXTRTtl3on89fVO.nfljtsv6qko","sequencer":"0055AED6DCD90281E5"}}}]}"#
     }
 }
@@ -0,0 +1,41 @@
+use serde::Deserialize;
+
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct SnsNotification {
+    #[serde(rename(deserialize = "TopicArn"))]
+    topic_arn: String,
+    #[serde(rename(deserialize = "Message"))]
+    message: String,
+}
+
+impl SnsNotification {
+    pub fn topic_arn(&self) -> &str {
+        &self.topic_arn
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialises_sns_notification() {
+        let actual: SnsNotification = serde_json::from_str(notification()).unwrap();
+        assert_eq!(actual, expected())
+    }
+
+    fn expected() -> SnsNotification {
+        SnsNotification {
+            topic_arn: String::from("arn:aws:sns:us-east-1:000000000000:test-topic"),
+            message: String::from(r#"{"Records":[]}"#),
+        }
+    }
+
+    fn notification() -> &'static str {
+        r#"{"Type":"Notification","MessageId":"abcd","TopicArn":"arn:aws:sns:us-east-1:000000000000:test-topic","Subject":"Amazon S3 Notification","Message":"{\"Records\":[]}","Timestamp":"2024-08-10T12:53:00.000Z"}"#
+    }
+}
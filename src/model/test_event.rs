@@ -0,0 +1,42 @@
+use serde::Deserialize;
+
+/// Sent by S3 once when a bucket notification is first configured.
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct TestEvent {
+    #[serde(rename(deserialize = "Event"))]
+    event: String,
+    #[serde(rename(deserialize = "Bucket"))]
+    bucket: String,
+}
+
+impl TestEvent {
+    pub fn event(&self) -> &str {
+        &self.event
+    }
+
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialises_test_event() {
+        let actual: TestEvent = serde_json::from_str(event()).unwrap();
+        assert_eq!(actual, expected())
+    }
+
+    fn expected() -> TestEvent {
+        TestEvent {
+            event: String::from("s3:TestEvent"),
+            bucket: String::from("test-bucket"),
+        }
+    }
+
+    fn event() -> &'static str {
+        r#"{"Service":"Amazon S3","Event":"s3:TestEvent","Time":"2024-08-10T12:53:00.000Z","Bucket":"test-bucket","RequestId":"5582815E1AEA5ADF","HostId":"8cLeGAmw098X5cv4Zkwcmo8vvZa3eH3eKxsPzbB9wrR+YstdA6Knx4Ip8EXAMPLE"}"#
+    }
+}
@@ -0,0 +1,80 @@
+use axum::Router;
+#[cfg(not(feature = "swagger-ui"))]
+use axum::{routing::get, Json};
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+
+/// The OpenAPI document for the HTTP API, built from the handler annotations.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Event pipeline API"),
+    paths(
+        crate::ping,
+        crate::ready,
+        crate::metrics,
+        crate::stream,
+        crate::collisions,
+        crate::list_schemas,
+        crate::summary,
+        crate::inspect,
+        crate::dry_run,
+        crate::pause,
+        crate::resume,
+        crate::flush,
+        crate::config_view,
+        crate::reload,
+    ),
+    modifiers(&Bearer)
+)]
+pub struct ApiDoc;
+
+/// Tokens are either static or JWTs. Operations list the scope they need.
+struct Bearer;
+
+impl Modify for Bearer {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+/// Serves the document at `/openapi.json`, with a Swagger UI page at
+/// `/swagger-ui` when built with the `swagger-ui` feature.
+pub fn router() -> Router {
+    #[cfg(feature = "swagger-ui")]
+    {
+        Router::new().merge(
+            utoipa_swagger_ui::SwaggerUi::new("/swagger-ui")
+                .url("/openapi.json", ApiDoc::openapi()),
+        )
+    }
+    #[cfg(not(feature = "swagger-ui"))]
+    {
+        let document = ApiDoc::openapi();
+        Router::new().route("/openapi.json", get(move || async move { Json(document) }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn documents_routes_and_scopes() {
+        let document = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        let summary = &document["paths"]["/batch/summary"]["get"];
+        assert_eq!(summary["security"][0]["bearer"][0], "read");
+        assert!(document["paths"]["/admin/pause/{component}"]["post"].is_object());
+        assert!(document["components"]["schemas"]["Summary"].is_object());
+        assert_eq!(
+            document["components"]["securitySchemes"]["bearer"]["scheme"],
+            "bearer"
+        );
+    }
+}
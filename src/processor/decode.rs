@@ -0,0 +1,254 @@
+use std::{
+    fmt::{self, Display},
+    io::{self, BufRead, BufReader, Read},
+};
+
+use flate2::read::MultiGzDecoder;
+
+use crate::model::Event;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Identity,
+    Gzip,
+    Zstd,
+}
+
+impl Encoding {
+    /// Prefers the object's `Content-Encoding`, falling back to its extension.
+    pub fn detect(content_encoding: Option<&str>, key: &str) -> Self {
+        match content_encoding.map(str::to_ascii_lowercase).as_deref() {
+            Some("gzip") | Some("x-gzip") => return Encoding::Gzip,
+            Some("zstd") => return Encoding::Zstd,
+            _ => {}
+        }
+
+        if key.ends_with(".gz") || key.ends_with(".gzip") {
+            Encoding::Gzip
+        } else if key.ends_with(".zst") || key.ends_with(".zstd") {
+            Encoding::Zstd
+        } else {
+            Encoding::Identity
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    JsonLines,
+}
+
+impl Format {
+    pub fn detect(content_type: Option<&str>, key: &str) -> Self {
+        let content_type = content_type.map(str::to_ascii_lowercase);
+        if matches!(
+            content_type.as_deref(),
+            Some("application/x-ndjson")
+                | Some("application/jsonl")
+                | Some("application/jsonlines")
+        ) {
+            return Format::JsonLines;
+        }
+
+        let key = [".gz", ".gzip", ".zst", ".zstd"]
+            .iter()
+            .find_map(|extension| key.strip_suffix(extension))
+            .unwrap_or(key);
+        if key.ends_with(".jsonl") || key.ends_with(".ndjson") {
+            Format::JsonLines
+        } else {
+            Format::Json
+        }
+    }
+}
+
+/// An event read from an object, or the reason it could not be read. Events
+/// from JSON Lines objects carry their 1-based line number.
+#[derive(Debug)]
+pub struct Extracted {
+    line: Option<usize>,
+    event: Result<Event, serde_json::Error>,
+}
+
+impl Extracted {
+    pub fn new(line: Option<usize>, event: Result<Event, serde_json::Error>) -> Self {
+        Self { line, event }
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    pub fn event(&self) -> &Result<Event, serde_json::Error> {
+        &self.event
+    }
+}
+
+pub fn decoder<'a, R: Read + 'a>(encoding: &Encoding, reader: R) -> io::Result<Box<dyn Read + 'a>> {
+    let decoder: Box<dyn Read + 'a> = match encoding {
+        Encoding::Identity => Box::new(reader),
+        Encoding::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        Encoding::Zstd => Box::new(zstd::Decoder::new(reader)?),
+    };
+    Ok(decoder)
+}
+
+/// Reads every event in the object. Only I/O errors fail the whole read; an
+/// invalid event is returned alongside the valid ones.
+pub fn read<R: Read>(format: &Format, reader: R) -> io::Result<Vec<Extracted>> {
+    match format {
+        Format::Json => match serde_json::from_reader(reader) {
+            Err(e) if e.is_io() => Err(e.into()),
+            event => Ok(vec![Extracted::new(None, event)]),
+        },
+        Format::JsonLines => read_lines(reader),
+    }
+}
+
+fn read_lines<R: Read>(reader: R) -> io::Result<Vec<Extracted>> {
+    let mut reader = BufReader::new(reader);
+    let mut extracted = Vec::new();
+    let mut buffer = Vec::new();
+    let mut line = 0;
+
+    loop {
+        buffer.clear();
+        if reader.read_until(b'\n', &mut buffer)? == 0 {
+            return Ok(extracted);
+        }
+        line += 1;
+
+        if buffer.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        extracted.push(Extracted::new(Some(line), serde_json::from_slice(&buffer)));
+    }
+}
+
+#[derive(Debug)]
+pub struct LimitExceeded;
+
+impl Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "object exceeds maximum size")
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// Fails the read once more than `remaining` bytes have been consumed, in case
+/// the body is longer than its advertised length or decompresses too far.
+pub struct Limited<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R> Limited<R> {
+    pub fn new(inner: R, limit: u64) -> Self {
+        Self {
+            inner,
+            remaining: limit,
+        }
+    }
+}
+
+impl<R: Read> Read for Limited<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Reads one byte past the limit so an object of exactly the limit passes.
+        let max = buf
+            .len()
+            .min(usize::try_from(self.remaining + 1).unwrap_or(usize::MAX));
+        let read = self.inner.read(&mut buf[..max])?;
+        if read as u64 > self.remaining {
+            return Err(io::Error::other(LimitExceeded));
+        }
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    const EVENT: &str = r#"{"request":{"source":"somewhere","answers":{"first_name":"Tim"}},"response":{"id":"1234"}}"#;
+
+    #[test]
+    fn detects_encoding_from_header_before_extension() {
+        assert_eq!(Encoding::detect(Some("gzip"), "1234.json"), Encoding::Gzip);
+        assert_eq!(Encoding::detect(None, "1234.json.zst"), Encoding::Zstd);
+        assert_eq!(Encoding::detect(None, "1234.json"), Encoding::Identity);
+    }
+
+    #[test]
+    fn detects_json_lines_behind_compression_extension() {
+        assert_eq!(Format::detect(None, "1234.jsonl.gz"), Format::JsonLines);
+        assert_eq!(
+            Format::detect(Some("application/x-ndjson"), "1234"),
+            Format::JsonLines
+        );
+        assert_eq!(Format::detect(None, "1234.json.gz"), Format::Json);
+    }
+
+    #[test]
+    fn reads_gzip_json_lines() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        write!(encoder, "{}\n\n{}\n", EVENT, EVENT).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let reader = decoder(&Encoding::Gzip, compressed.as_slice()).unwrap();
+        let actual = read(&Format::JsonLines, reader).unwrap();
+
+        let lines: Vec<Option<usize>> = actual.iter().map(Extracted::line).collect();
+        assert_eq!(lines, vec![Some(1), Some(3)]);
+        assert!(actual.iter().all(|extracted| extracted.event().is_ok()));
+    }
+
+    #[test]
+    fn reads_zstd_json() {
+        let compressed = zstd::encode_all(EVENT.as_bytes(), 0).unwrap();
+
+        let reader = decoder(&Encoding::Zstd, compressed.as_slice()).unwrap();
+        let actual = read(&Format::Json, reader).unwrap();
+
+        assert_eq!(actual.len(), 1);
+        assert!(actual[0].event().is_ok());
+    }
+
+    #[test]
+    fn keeps_reading_after_invalid_line() {
+        let content = format!("{}\nnot json\n{}", EVENT, EVENT);
+        let actual = read(&Format::JsonLines, content.as_bytes()).unwrap();
+
+        let failed: Vec<Option<usize>> = actual
+            .iter()
+            .filter(|extracted| extracted.event().is_err())
+            .map(Extracted::line)
+            .collect();
+        assert_eq!(actual.len(), 3);
+        assert_eq!(failed, vec![Some(2)]);
+    }
+
+    #[test]
+    fn reads_within_limit() {
+        let mut actual = String::new();
+        Limited::new("abcd".as_bytes(), 4)
+            .read_to_string(&mut actual)
+            .unwrap();
+        assert_eq!(actual, "abcd")
+    }
+
+    #[test]
+    fn fails_beyond_limit() {
+        let mut actual = String::new();
+        let error = Limited::new("abcde".as_bytes(), 4)
+            .read_to_string(&mut actual)
+            .unwrap_err();
+        assert!(error.get_ref().unwrap().is::<LimitExceeded>())
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::Path,
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::DedupConfig;
+
+/// When an event key was first seen, as persisted between restarts.
+#[derive(Debug, Serialize, Deserialize)]
+struct Seen {
+    key: String,
+    seen: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct Index {
+    seen: HashMap<String, DateTime<Utc>>,
+    // Oldest first, so expired and excess keys are dropped from the front.
+    order: VecDeque<(DateTime<Utc>, String)>,
+}
+
+impl Index {
+    fn expire(&mut self, cutoff: DateTime<Utc>, max_entries: usize) {
+        while let Some((seen, key)) = self.order.front() {
+            if *seen > cutoff && self.order.len() <= max_entries {
+                break;
+            }
+            self.seen.remove(key);
+            self.order.pop_front();
+        }
+    }
+
+    fn insert(&mut self, key: String, seen: DateTime<Utc>) {
+        self.seen.insert(key.clone(), seen);
+        self.order.push_back((seen, key));
+    }
+}
+
+/// Remembers which events have been added to a batch within the window, so
+/// redelivered events are not written twice.
+pub struct Deduplicator {
+    config: DedupConfig,
+    index: Mutex<Index>,
+}
+
+impl Deduplicator {
+    pub fn new(config: DedupConfig) -> Self {
+        let index = match config.path() {
+            Some(path) if config.enabled() => load(path),
+            _ => Index::default(),
+        };
+        Self {
+            config,
+            index: Mutex::new(index),
+        }
+    }
+
+    pub fn key_field(&self) -> Option<&str> {
+        self.config.key_field()
+    }
+
+    /// Records the key and returns whether it was new. Always true when
+    /// deduplication is disabled.
+    pub fn first_seen(&self, source: &str, key: &str, now: DateTime<Utc>) -> bool {
+        if !self.config.enabled() {
+            return true;
+        }
+
+        let mut index = self.index.lock().unwrap();
+        index.expire(self.cutoff(now), self.config.max_entries());
+
+        let key = format!("{}/{}", source, key);
+        if index.seen.contains_key(&key) {
+            return false;
+        }
+        index.insert(key, now);
+        index.expire(self.cutoff(now), self.config.max_entries());
+        true
+    }
+
+    /// Writes the index to the configured path, replacing the previous file
+    /// only once the new one is complete.
+    pub fn persist(&self) {
+        let Some(path) = self.config.path().filter(|_| self.config.enabled()) else {
+            return;
+        };
+
+        let entries: Vec<Seen> = {
+            let mut index = self.index.lock().unwrap();
+            index.expire(self.cutoff(Utc::now()), self.config.max_entries());
+            index
+                .order
+                .iter()
+                .map(|(seen, key)| Seen {
+                    key: key.clone(),
+                    seen: *seen,
+                })
+                .collect()
+        };
+
+        let temporary = path.with_extension("tmp");
+        let result = fs::write(&temporary, serde_json::to_vec(&entries).unwrap())
+            .and_then(|_| fs::rename(&temporary, path));
+        match result {
+            Ok(_) => tracing::debug!("Persisted {} dedup keys", entries.len()),
+            Err(e) => tracing::error!("Failed to persist dedup index. Error: {}", e),
+        }
+    }
+
+    fn cutoff(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        Duration::from_std(self.config.window())
+            .ok()
+            .and_then(|window| now.checked_sub_signed(window))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC)
+    }
+}
+
+fn load(path: &Path) -> Index {
+    let mut index = Index::default();
+    let entries: Vec<Seen> = match fs::read(path) {
+        Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring unreadable dedup index. Error: {}", e);
+            Vec::new()
+        }),
+        Err(_) => return index,
+    };
+
+    tracing::info!(
+        "Loaded {} dedup keys from '{}'",
+        entries.len(),
+        path.display()
+    );
+    for entry in entries {
+        index.insert(entry.key, entry.seen);
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_keys_seen_within_window() {
+        let deduplicator = deduplicator(r#"{"window_seconds":60}"#);
+        let now = Utc::now();
+
+        assert!(deduplicator.first_seen("somewhere", "1234", now));
+        assert!(!deduplicator.first_seen("somewhere", "1234", now + Duration::seconds(30)));
+        assert!(deduplicator.first_seen("elsewhere", "1234", now));
+        assert!(deduplicator.first_seen("somewhere", "1234", now + Duration::seconds(61)));
+    }
+
+    #[test]
+    fn forgets_oldest_keys_beyond_capacity() {
+        let deduplicator = deduplicator(r#"{"max_entries":2}"#);
+        let now = Utc::now();
+
+        deduplicator.first_seen("somewhere", "1", now);
+        deduplicator.first_seen("somewhere", "2", now);
+        deduplicator.first_seen("somewhere", "3", now);
+
+        assert!(deduplicator.first_seen("somewhere", "1", now));
+        assert!(!deduplicator.first_seen("somewhere", "3", now));
+    }
+
+    #[test]
+    fn allows_everything_when_disabled() {
+        let deduplicator = deduplicator(r#"{"enabled":false}"#);
+        let now = Utc::now();
+
+        assert!(deduplicator.first_seen("somewhere", "1234", now));
+        assert!(deduplicator.first_seen("somewhere", "1234", now));
+    }
+
+    #[test]
+    fn restores_persisted_index() {
+        let path = std::env::temp_dir().join(format!("dedup-{}.json", uuid::Uuid::new_v4()));
+        let config = format!(r#"{{"path":{:?}}}"#, path.display().to_string());
+
+        let first = deduplicator(&config);
+        first.first_seen("somewhere", "1234", Utc::now());
+        first.persist();
+
+        let restored = deduplicator(&config);
+        assert!(!restored.first_seen("somewhere", "1234", Utc::now()));
+        fs::remove_file(path).unwrap();
+    }
+
+    fn deduplicator(config: &str) -> Deduplicator {
+        Deduplicator::new(serde_json::from_str(config).unwrap())
+    }
+}
@@ -0,0 +1,83 @@
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use utoipa::ToSchema;
+
+use crate::model::{Event, Notification};
+
+use super::{Error, Pipeline, Recording};
+
+/// The flattened record for a sample event before and after field mapping and
+/// redaction.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DryRun {
+    source: String,
+    before: Map<String, Value>,
+    after: Map<String, Value>,
+}
+
+pub fn dry_run(event: &Event, pipeline: &Pipeline) -> Result<DryRun, Error> {
+    let notification = Notification::builder()
+        .message_id("dry-run")
+        .receipt_handle("dry-run")
+        .created(Utc::now())
+        .bucket("dry-run")
+        .key("dry-run.json")
+        .build();
+
+    let before = pipeline.flatten(event, &notification, Recording::Off)?;
+    let mut after = before.clone();
+    pipeline.map(&mut after, event.request().source(), Recording::Off)?;
+
+    Ok(DryRun {
+        source: String::from(event.request().source()),
+        before,
+        after,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn shows_record_before_and_after_mapping() {
+        let event: Event = serde_json::from_value(json!({
+            "request": {"source": "somewhere", "answers": {"firstName": "Tim"}},
+            "response": {"id": "1234"},
+        }))
+        .unwrap();
+        let config = serde_json::from_value(json!({
+            "mapping": {
+                "default": [{"op": "drop", "field": "firstName"}],
+                "sources": {"somewhere": [{"op": "rename", "from": "firstName", "to": "first_name"}]},
+            },
+        }))
+        .unwrap();
+        let pipeline = Pipeline::new(&config, None).unwrap();
+
+        let actual = dry_run(&event, &pipeline).unwrap();
+
+        assert_eq!(actual.before["firstName"], json!("Tim"));
+        assert_eq!(actual.after["first_name"], json!("Tim"));
+        assert!(!actual.after.contains_key("firstName"));
+    }
+
+    #[test]
+    fn does_not_count_collisions() {
+        let event: Event = serde_json::from_value(json!({
+            "request": {"source": "dry-run-only", "answers": {"id": "mine"}},
+            "response": {"id": "1234"},
+        }))
+        .unwrap();
+        let pipeline = Pipeline::new(&Default::default(), None).unwrap();
+
+        let actual = dry_run(&event, &pipeline).unwrap();
+
+        assert_eq!(actual.before["id_2"], json!("mine"));
+        let counts = serde_json::to_value(crate::processor::collision_counts()).unwrap();
+        assert!(!counts.to_string().contains("dry-run-only"));
+    }
+}
@@ -0,0 +1,76 @@
+use std::fmt::{self, Display};
+
+#[derive(Debug)]
+pub enum Error {
+    Extract(String),
+    TooLarge { size: Option<u64>, limit: u64 },
+    Decode(String),
+    Deserialise { line: Option<usize>, error: String },
+    Collision { key: String },
+    ReservedCollision { key: String },
+    Mapping(String),
+    Schema { source: String, errors: Vec<String> },
+    DeadLetter(String),
+}
+
+impl Error {
+    /// Permanent errors will fail the same way on every retry, so the object
+    /// is dead-lettered rather than released back to the queue.
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            Error::Extract(_) | Error::DeadLetter(_) => false,
+            Error::TooLarge { .. }
+            | Error::Decode(_)
+            | Error::Deserialise { .. }
+            | Error::Collision { .. }
+            | Error::ReservedCollision { .. }
+            | Error::Mapping(_)
+            | Error::Schema { .. } => true,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Extract(message) => write!(f, "failed to extract event: {}", message),
+            Error::TooLarge {
+                size: Some(size),
+                limit,
+            } => write!(
+                f,
+                "object size {} bytes exceeds maximum {} bytes",
+                size, limit
+            ),
+            Error::TooLarge { size: None, limit } => {
+                write!(f, "object exceeds maximum {} bytes", limit)
+            }
+            Error::Decode(message) => write!(f, "failed to decode object: {}", message),
+            Error::Deserialise {
+                line: Some(line),
+                error,
+            } => write!(f, "failed to deserialise event on line {}: {}", line, error),
+            Error::Deserialise { line: None, error } => {
+                write!(f, "failed to deserialise event: {}", error)
+            }
+            Error::Collision { key } => {
+                write!(f, "more than one answer flattens to key '{}'", key)
+            }
+            Error::ReservedCollision { key } => {
+                write!(f, "answer '{}' collides with a reserved field", key)
+            }
+            Error::Mapping(message) => write!(f, "failed to map fields: {}", message),
+            Error::Schema { source, errors } => write!(
+                f,
+                "event does not match schema for source '{}': {}",
+                source,
+                errors.join("; ")
+            ),
+            Error::DeadLetter(message) => {
+                write!(f, "failed to dead-letter notification: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
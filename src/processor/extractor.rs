@@ -1,26 +1,107 @@
-use aws_sdk_s3::Client;
+use std::io;
+
+use aws_sdk_s3::{error::DisplayErrorContext, Client};
 use axum::async_trait;
+use tokio_util::io::SyncIoBridge;
 
 use crate::model::Notification;
 
+use super::{
+    decode::{self, Encoding, Extracted, LimitExceeded, Limited},
+    Error,
+};
+
 #[async_trait]
 pub trait EventExtractor {
-    async fn extract(&self, notification: &Notification) -> Vec<u8>;
+    async fn extract(&self, notification: &Notification) -> Result<Vec<Extracted>, Error>;
+}
+
+pub struct S3Extractor {
+    client: Client,
+    max_object_size: u64,
+}
+
+impl S3Extractor {
+    pub fn new(client: Client, max_object_size: u64) -> Self {
+        Self {
+            client,
+            max_object_size,
+        }
+    }
+
+    fn check_size(&self, size: i64) -> Result<(), Error> {
+        let size = u64::try_from(size).unwrap_or_default();
+        if size > self.max_object_size {
+            return Err(Error::TooLarge {
+                size: Some(size),
+                limit: self.max_object_size,
+            });
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
-impl EventExtractor for Client {
-    async fn extract(&self, notification: &Notification) -> Vec<u8> {
-        self.get_object()
+impl EventExtractor for S3Extractor {
+    async fn extract(&self, notification: &Notification) -> Result<Vec<Extracted>, Error> {
+        tracing::debug!(
+            "Fetching 's3://{}/{}' (version: {:?}, size: {:?}, eTag: {:?}, sequencer: {:?})",
+            notification.bucket(),
+            notification.key(),
+            notification.version_id(),
+            notification.size(),
+            notification.e_tag(),
+            notification.sequencer()
+        );
+
+        // Checked before the request so oversized objects are never fetched.
+        if let Some(size) = notification.size() {
+            self.check_size(size)?;
+        }
+
+        let output = self
+            .client
+            .get_object()
             .bucket(notification.bucket())
             .key(notification.key())
+            .set_version_id(notification.version_id().map(String::from))
             .send()
             .await
-            .unwrap()
-            .body
-            .collect()
-            .await
-            .unwrap()
-            .to_vec()
+            .map_err(|e| Error::Extract(DisplayErrorContext(e).to_string()))?;
+
+        if let Some(size) = output.content_length() {
+            self.check_size(size)?;
+        }
+
+        let encoding = Encoding::detect(output.content_encoding(), notification.key());
+        let format = decode::Format::detect(output.content_type(), notification.key());
+        let limit = self.max_object_size;
+        let body = SyncIoBridge::new(output.body.into_async_read());
+
+        // The limit applies to the decompressed content as well, so a small
+        // compressed object cannot expand without bound.
+        tokio::task::spawn_blocking(move || {
+            let decoded = decode::decoder(&encoding, Limited::new(body, limit))?;
+            decode::read(&format, Limited::new(decoded, limit))
+        })
+        .await
+        .map_err(|e| Error::Extract(e.to_string()))?
+        .map_err(|e| error_from(e, limit))
+    }
+}
+
+fn error_from(error: io::Error, limit: u64) -> Error {
+    if error
+        .get_ref()
+        .is_some_and(|inner| inner.is::<LimitExceeded>())
+    {
+        return Error::TooLarge { size: None, limit };
+    }
+
+    match error.kind() {
+        io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => {
+            Error::Decode(error.to_string())
+        }
+        _ => Error::Extract(error.to_string()),
     }
 }
@@ -0,0 +1,216 @@
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+
+use super::Error;
+
+/// A single step applied to a flattened record, in the order configured.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Rule {
+    Rename { from: String, to: String },
+    Drop { field: String },
+    Default { field: String, value: Value },
+    Cast { field: String, to: CastType },
+    Copy { from: String, to: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CastType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+}
+
+/// Rejects rules that would change or remove the metadata fields the rest of
+/// the pipeline depends on. Copying from them is allowed.
+pub fn check<'a>(
+    rules: impl IntoIterator<Item = &'a Rule>,
+    reserved: &[String],
+) -> Result<(), String> {
+    for rule in rules {
+        let targets = match rule {
+            Rule::Rename { from, to } => vec![from, to],
+            Rule::Drop { field } | Rule::Default { field, .. } | Rule::Cast { field, .. } => {
+                vec![field]
+            }
+            Rule::Copy { to, .. } => vec![to],
+        };
+        if let Some(field) = targets.into_iter().find(|field| reserved.contains(field)) {
+            return Err(format!(
+                "mapping rule {:?} targets reserved field '{}'",
+                rule, field
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub fn apply(record: &mut Map<String, Value>, rules: &[Rule]) -> Result<(), Error> {
+    for rule in rules {
+        match rule {
+            Rule::Rename { from, to } => {
+                if let Some(value) = record.remove(from) {
+                    record.insert(to.clone(), value);
+                }
+            }
+            Rule::Drop { field } => {
+                record.remove(field);
+            }
+            Rule::Default { field, value } => {
+                let current = record.entry(field.clone()).or_insert(Value::Null);
+                if current.is_null() {
+                    *current = value.clone();
+                }
+            }
+            Rule::Cast { field, to } => {
+                if let Some(value) = record.get_mut(field) {
+                    *value = cast(value, *to).ok_or_else(|| {
+                        Error::Mapping(format!(
+                            "cannot cast field '{}' value {} to {:?}",
+                            field, value, to
+                        ))
+                    })?;
+                }
+            }
+            Rule::Copy { from, to } => {
+                if let Some(value) = record.get(from).cloned() {
+                    record.insert(to.clone(), value);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Nulls are left alone so a missing answer stays missing whatever its type.
+fn cast(value: &Value, to: CastType) -> Option<Value> {
+    if value.is_null() {
+        return Some(Value::Null);
+    }
+
+    match (to, value) {
+        (CastType::String, Value::String(_)) => Some(value.clone()),
+        (CastType::String, other) => Some(Value::String(other.to_string())),
+
+        (CastType::Integer, Value::Number(number)) => number
+            .as_i64()
+            .or_else(|| {
+                number
+                    .as_f64()
+                    .filter(|f| f.fract() == 0.0)
+                    .map(|f| f as i64)
+            })
+            .map(Value::from),
+        (CastType::Integer, Value::String(text)) => {
+            text.trim().parse::<i64>().ok().map(Value::from)
+        }
+        (CastType::Integer, Value::Bool(flag)) => Some(Value::from(i64::from(*flag))),
+
+        (CastType::Float, Value::Number(number)) => number
+            .as_f64()
+            .and_then(Number::from_f64)
+            .map(Value::Number),
+        (CastType::Float, Value::String(text)) => text
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number),
+
+        (CastType::Boolean, Value::Bool(_)) => Some(value.clone()),
+        (CastType::Boolean, Value::String(text)) => match text.trim().to_ascii_lowercase().as_str()
+        {
+            "true" | "yes" | "1" => Some(Value::Bool(true)),
+            "false" | "no" | "0" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        (CastType::Boolean, Value::Number(number)) => match number.as_i64() {
+            Some(1) => Some(Value::Bool(true)),
+            Some(0) => Some(Value::Bool(false)),
+            _ => None,
+        },
+
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn applies_rules_in_order() {
+        let mut record = record(json!({"firstName": "Tim", "age": "42", "internal": true}));
+        let rules: Vec<Rule> = serde_json::from_value(json!([
+            {"op": "rename", "from": "firstName", "to": "first_name"},
+            {"op": "copy", "from": "first_name", "to": "display_name"},
+            {"op": "drop", "field": "internal"},
+            {"op": "default", "field": "country", "value": "GB"},
+            {"op": "cast", "field": "age", "to": "integer"},
+        ]))
+        .unwrap();
+
+        apply(&mut record, &rules).unwrap();
+
+        assert_eq!(
+            Value::Object(record),
+            json!({"first_name": "Tim", "display_name": "Tim", "age": 42, "country": "GB"})
+        )
+    }
+
+    #[test]
+    fn rejects_rules_targeting_reserved_fields() {
+        let reserved = vec![String::from("id"), String::from("s3_uri")];
+        let rules: Vec<Rule> = serde_json::from_value(json!([
+            {"op": "copy", "from": "id", "to": "event_id"},
+            {"op": "rename", "from": "s3_uri", "to": "uri"},
+        ]))
+        .unwrap();
+
+        let actual = check(&rules, &reserved);
+
+        assert!(actual.unwrap_err().contains("reserved field 's3_uri'"));
+        assert_eq!(check(&rules[..1], &reserved), Ok(()));
+    }
+
+    #[test]
+    fn default_replaces_null() {
+        let mut record = record(json!({"country": null}));
+        let rules = vec![Rule::Default {
+            field: String::from("country"),
+            value: json!("GB"),
+        }];
+
+        apply(&mut record, &rules).unwrap();
+
+        assert_eq!(record["country"], json!("GB"))
+    }
+
+    #[test]
+    fn casts_between_types() {
+        assert_eq!(cast(&json!("yes"), CastType::Boolean), Some(json!(true)));
+        assert_eq!(cast(&json!(3.0), CastType::Integer), Some(json!(3)));
+        assert_eq!(cast(&json!("2.5"), CastType::Float), Some(json!(2.5)));
+        assert_eq!(cast(&json!(false), CastType::String), Some(json!("false")));
+        assert_eq!(cast(&json!(null), CastType::Integer), Some(json!(null)));
+    }
+
+    #[test]
+    fn rejects_invalid_cast() {
+        let mut record = record(json!({"age": "forty"}));
+        let rules = vec![Rule::Cast {
+            field: String::from("age"),
+            to: CastType::Integer,
+        }];
+
+        assert!(apply(&mut record, &rules).is_err())
+    }
+
+    fn record(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().to_owned()
+    }
+}
@@ -1,66 +1,414 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::BTreeSet, sync::Arc};
 
 use axum::async_trait;
-use chrono::{DateTime, Utc};
-pub use extractor::EventExtractor;
+use chrono::Utc;
+pub use dedup::Deduplicator;
+pub use dry_run::{dry_run, DryRun};
+pub use error::Error;
+pub use extractor::{EventExtractor, S3Extractor};
+pub use mapping::Rule;
+pub use pipeline::{Pipeline, Recording, SharedPipeline};
+pub use redaction::{DetectorRule, FieldRule, Redactor};
+use serde_json::{Map, Value};
+pub use transform::{collision_counts, CollisionCount, CollisionPolicy, KeyScheme, ReservedFields};
+pub use validation::{SchemaInfo, Schemas};
 
 use crate::{
+    activity::{self, Activity},
     batch,
+    deadletter::DeadLetterer,
+    metrics,
     model::{Event, Notification},
 };
 
+mod decode;
+mod dedup;
+mod dry_run;
+mod error;
 mod extractor;
+mod mapping;
+mod pipeline;
+mod redaction;
 mod transform;
+mod validation;
 
 #[async_trait]
 pub trait NotificationProcessor {
-    async fn process(&self, notification: &Notification);
+    async fn process(&self, notification: &Notification) -> Result<(), Error>;
 }
 
 pub struct NotificationProcessorImpl {
     extractor: Box<dyn EventExtractor + Sync + Send>,
     batch_store: Arc<dyn batch::Store + Sync + Send>,
+    dead_letterer: Arc<dyn DeadLetterer + Sync + Send>,
+    pipeline: Arc<SharedPipeline>,
+    schemas: Arc<Schemas>,
+    deduplicator: Arc<Deduplicator>,
 }
 
 impl NotificationProcessorImpl {
     pub fn new(
         extractor: Box<dyn EventExtractor + Sync + Send>,
         batch_store: Arc<dyn batch::Store + Sync + Send>,
+        dead_letterer: Arc<dyn DeadLetterer + Sync + Send>,
+        pipeline: Arc<SharedPipeline>,
+        schemas: Arc<Schemas>,
+        deduplicator: Arc<Deduplicator>,
     ) -> Self {
         Self {
             extractor,
             batch_store,
+            dead_letterer,
+            pipeline,
+            schemas,
+            deduplicator,
         }
     }
 }
 
 #[async_trait]
 impl NotificationProcessor for NotificationProcessorImpl {
-    async fn process(&self, notification: &Notification) {
-        let bytes = self.extractor.extract(notification).await;
-        let event = NotificationProcessorImpl::deserialise(&bytes);
-        let flattened = transform::apply(&event, notification);
-        let json = NotificationProcessorImpl::serialise(&flattened);
-        let entry = NotificationProcessorImpl::entry(
-            event.request().source(),
-            notification.created(),
-            &json,
-        );
-        self.batch_store.add(entry);
+    async fn process(&self, notification: &Notification) -> Result<(), Error> {
+        let mut sources = BTreeSet::new();
+        let result = self.process_events(notification, &mut sources).await;
+
+        let message_id = String::from(notification.message_id());
+        let bucket = String::from(notification.bucket());
+        let key = String::from(notification.key());
+        let sources = sources.into_iter().collect();
+        activity::publish(match &result {
+            Ok(()) => Activity::NotificationProcessed {
+                message_id,
+                bucket,
+                key,
+                sources,
+            },
+            Err(e) => Activity::NotificationFailed {
+                message_id,
+                bucket,
+                key,
+                sources,
+                error: e.to_string(),
+            },
+        });
+        result
     }
 }
 
 impl NotificationProcessorImpl {
-    fn deserialise(bytes: &[u8]) -> Event {
-        serde_json::from_slice(bytes).unwrap()
+    async fn process_events(
+        &self,
+        notification: &Notification,
+        sources: &mut BTreeSet<String>,
+    ) -> Result<(), Error> {
+        match self.extractor.extract(notification).await {
+            Ok(extracted) => {
+                let pipeline = self.pipeline.current();
+                // A bad line is reported on its own so the rest of the object
+                // is still processed.
+                for item in extracted {
+                    match item.event() {
+                        Ok(event) => {
+                            sources.insert(String::from(event.request().source()));
+                            if let Err(e) = self.add(&pipeline, event, notification) {
+                                self.reject(notification, &e).await?;
+                            }
+                        }
+                        Err(e) => {
+                            let error = Error::Deserialise {
+                                line: item.line(),
+                                error: e.to_string(),
+                            };
+                            self.reject(notification, &error).await?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+            Err(e) if e.is_permanent() => self.reject(notification, &e).await,
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn reject(&self, notification: &Notification, error: &Error) -> Result<(), Error> {
+        metrics::increment("notifications_rejected_total", &[]);
+        self.dead_letterer
+            .dead_letter(notification, &error.to_string())
+            .await
+            .map_err(Error::DeadLetter)
+    }
+
+    fn add(
+        &self,
+        pipeline: &Pipeline,
+        event: &Event,
+        notification: &Notification,
+    ) -> Result<(), Error> {
+        self.schemas.validate(event.request())?;
+        let source = event.request().source();
+        let mut flattened = pipeline.flatten(event, notification, Recording::On)?;
+        // Keyed before mapping and redaction, which may rename, drop or mask
+        // the key field.
+        let key = self.dedup_key(event, &flattened);
+        pipeline.map(&mut flattened, source, Recording::On)?;
+
+        let duplicate = matches!(
+            key,
+            Some(key) if !self.deduplicator.first_seen(source, &key, Utc::now())
+        );
+        if duplicate {
+            tracing::debug!(
+                "Skipping duplicate event '{}' from '{}'",
+                event.response().id(),
+                source
+            );
+            metrics::increment("duplicates_skipped_total", &[("source", source)]);
+            return Ok(());
+        }
+        let json = NotificationProcessorImpl::serialise(&flattened);
+        let entry = NotificationProcessorImpl::entry(event.request().source(), notification, &json);
+        self.batch_store.add(entry);
+        Ok(())
+    }
+
+    // Events without the configured key field are never treated as duplicates.
+    fn dedup_key(&self, event: &Event, flattened: &Map<String, Value>) -> Option<String> {
+        match self.deduplicator.key_field() {
+            Some(field) => match flattened.get(field) {
+                Some(Value::String(key)) => Some(key.clone()),
+                Some(Value::Null) | None => None,
+                Some(other) => Some(other.to_string()),
+            },
+            None => Some(String::from(event.response().id())),
+        }
     }
 
-    fn serialise(flattened: &HashMap<String, String>) -> String {
+    fn serialise(flattened: &Map<String, Value>) -> String {
         serde_json::to_string(flattened).unwrap()
     }
 
-    fn entry(source: &str, created: &DateTime<Utc>, json: &str) -> batch::Entry {
+    fn entry(source: &str, notification: &Notification, json: &str) -> batch::Entry {
+        let created = notification.created();
         let partition = batch::Partition::new(source, created.date_naive());
-        batch::Entry::new(partition, created, json)
+        batch::Entry::new(partition, created, notification.message_id(), json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use chrono::Utc;
+
+    use super::{decode::Extracted, *};
+    use crate::config::Config;
+
+    #[tokio::test]
+    async fn dead_letters_oversized_objects() {
+        let dead_letterer = Arc::new(RecordingDeadLetterer::default());
+        let processor = processor(
+            Box::new(FailingExtractor),
+            Arc::new(batch::StoreImpl::new()),
+            dead_letterer.clone(),
+        );
+
+        let actual = processor.process(&notification()).await;
+
+        assert!(actual.is_ok());
+        assert_eq!(
+            *dead_letterer.reasons.lock().unwrap(),
+            vec![String::from(
+                "object size 2048 bytes exceeds maximum 1024 bytes"
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn retries_when_dead_lettering_fails() {
+        let processor = processor(
+            Box::new(FailingExtractor),
+            Arc::new(batch::StoreImpl::new()),
+            Arc::new(UnavailableDeadLetterer),
+        );
+
+        let actual = processor.process(&notification()).await;
+
+        assert!(matches!(actual, Err(Error::DeadLetter(_))));
+        assert!(!actual.unwrap_err().is_permanent());
+    }
+
+    struct FailingExtractor;
+
+    #[async_trait]
+    impl EventExtractor for FailingExtractor {
+        async fn extract(&self, _notification: &Notification) -> Result<Vec<Extracted>, Error> {
+            Err(Error::TooLarge {
+                size: Some(2048),
+                limit: 1024,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn processes_valid_lines_and_reports_invalid_ones() {
+        let dead_letterer = Arc::new(RecordingDeadLetterer::default());
+        let batch_store = Arc::new(batch::StoreImpl::new());
+        let processor = processor(
+            Box::new(LinesExtractor),
+            batch_store.clone(),
+            dead_letterer.clone(),
+        );
+
+        let actual = processor.process(&notification()).await;
+
+        assert!(actual.is_ok());
+        let batches = batch::Store::batches(batch_store.as_ref());
+        assert_eq!(batches[0].record_count(), 2);
+        let reasons = dead_letterer.reasons.lock().unwrap();
+        assert_eq!(reasons.len(), 1);
+        assert!(reasons[0].starts_with("failed to deserialise event on line 2:"));
+    }
+
+    #[tokio::test]
+    async fn skips_redelivered_events() {
+        let batch_store = Arc::new(batch::StoreImpl::new());
+        let processor = processor(
+            Box::new(LinesExtractor),
+            batch_store.clone(),
+            Arc::new(RecordingDeadLetterer::default()),
+        );
+
+        processor.process(&notification()).await.unwrap();
+        processor.process(&notification()).await.unwrap();
+
+        let batches = batch::Store::batches(batch_store.as_ref());
+        assert_eq!(batches[0].record_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn keys_duplicates_before_redaction() {
+        let config: Config = serde_json::from_str(
+            r#"{
+                "dedup": {"key_field": "email"},
+                "redaction": {"fields": [{"pattern": "email", "action": "mask"}]}
+            }"#,
+        )
+        .unwrap();
+        let batch_store = Arc::new(batch::StoreImpl::new());
+        let processor = configured(
+            &config,
+            Box::new(EmailsExtractor),
+            batch_store.clone(),
+            Arc::new(RecordingDeadLetterer::default()),
+        );
+
+        processor.process(&notification()).await.unwrap();
+
+        let batches = batch::Store::batches(batch_store.as_ref());
+        assert_eq!(batches[0].record_count(), 2);
+        assert!(!batches[0].records()[0].contains("a@example.com"));
+    }
+
+    struct EmailsExtractor;
+
+    #[async_trait]
+    impl EventExtractor for EmailsExtractor {
+        async fn extract(&self, _notification: &Notification) -> Result<Vec<Extracted>, Error> {
+            let event = |id: &str, email: &str| {
+                serde_json::from_str(&format!(
+                    r#"{{"request":{{"source":"somewhere","answers":{{"email":"{}"}}}},"response":{{"id":"{}"}}}}"#,
+                    email, id
+                ))
+            };
+            Ok(vec![
+                Extracted::new(Some(1), event("1", "a@example.com")),
+                Extracted::new(Some(2), event("2", "b@example.com")),
+                Extracted::new(Some(3), event("3", "a@example.com")),
+            ])
+        }
+    }
+
+    struct LinesExtractor;
+
+    #[async_trait]
+    impl EventExtractor for LinesExtractor {
+        async fn extract(&self, _notification: &Notification) -> Result<Vec<Extracted>, Error> {
+            let event = |id: &str| {
+                serde_json::from_str(&format!(
+                    r#"{{"request":{{"source":"somewhere","answers":{{}}}},"response":{{"id":"{}"}}}}"#,
+                    id
+                ))
+            };
+            Ok(vec![
+                Extracted::new(Some(1), event("1")),
+                Extracted::new(Some(2), serde_json::from_str("not json")),
+                Extracted::new(Some(3), event("3")),
+            ])
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingDeadLetterer {
+        reasons: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl DeadLetterer for RecordingDeadLetterer {
+        async fn dead_letter(
+            &self,
+            _notification: &Notification,
+            reason: &str,
+        ) -> Result<(), String> {
+            self.reasons.lock().unwrap().push(String::from(reason));
+            Ok(())
+        }
+    }
+
+    struct UnavailableDeadLetterer;
+
+    #[async_trait]
+    impl DeadLetterer for UnavailableDeadLetterer {
+        async fn dead_letter(
+            &self,
+            _notification: &Notification,
+            _reason: &str,
+        ) -> Result<(), String> {
+            Err(String::from("unavailable"))
+        }
+    }
+
+    fn processor(
+        extractor: Box<dyn EventExtractor + Sync + Send>,
+        batch_store: Arc<dyn batch::Store + Sync + Send>,
+        dead_letterer: Arc<dyn DeadLetterer + Sync + Send>,
+    ) -> NotificationProcessorImpl {
+        configured(&Config::default(), extractor, batch_store, dead_letterer)
+    }
+
+    fn configured(
+        config: &Config,
+        extractor: Box<dyn EventExtractor + Sync + Send>,
+        batch_store: Arc<dyn batch::Store + Sync + Send>,
+        dead_letterer: Arc<dyn DeadLetterer + Sync + Send>,
+    ) -> NotificationProcessorImpl {
+        NotificationProcessorImpl::new(
+            extractor,
+            batch_store,
+            dead_letterer,
+            Arc::new(SharedPipeline::new(Pipeline::new(config, None).unwrap())),
+            Arc::new(Schemas::default()),
+            Arc::new(Deduplicator::new(config.dedup().clone())),
+        )
+    }
+
+    fn notification() -> Notification {
+        Notification::builder()
+            .message_id("some-message")
+            .receipt_handle("receipt")
+            .created(Utc::now())
+            .bucket("test-bucket")
+            .key("1234.json")
+            .size(Some(2048))
+            .build()
     }
 }
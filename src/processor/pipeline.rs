@@ -0,0 +1,134 @@
+use std::sync::{Arc, RwLock};
+
+use serde_json::{Map, Value};
+
+use crate::{
+    config::{Config, MappingConfig, TransformConfig},
+    model::{Event, Notification},
+};
+
+use super::{mapping, transform, Error, Redactor};
+
+type Record = Map<String, Value>;
+
+/// Whether a run counts towards the collision and redaction metrics. Dry runs
+/// don't, so sample events can't skew the figures shown to form authors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recording {
+    On,
+    Off,
+}
+
+/// The record stages that can be reconfigured while running.
+pub struct Pipeline {
+    transform: TransformConfig,
+    reserved: Vec<String>,
+    mapping: MappingConfig,
+    redactor: Redactor,
+}
+
+impl Pipeline {
+    pub fn new(config: &Config, key: Option<Vec<u8>>) -> Result<Self, String> {
+        let mut reserved = config.transform().reserved_fields().names();
+        reserved.extend(config.redaction().reserved_field().map(String::from));
+        mapping::check(config.mapping().rules(), &reserved)?;
+        Ok(Self {
+            transform: config.transform().clone(),
+            mapping: config.mapping().clone(),
+            redactor: Redactor::new(config.redaction().clone(), reserved.clone(), key)?,
+            reserved,
+        })
+    }
+
+    pub fn flatten(
+        &self,
+        event: &Event,
+        notification: &Notification,
+        recording: Recording,
+    ) -> Result<Record, Error> {
+        transform::apply(
+            event,
+            notification,
+            &self.transform,
+            &self.reserved,
+            recording,
+        )
+    }
+
+    /// Applies the source's mapping rules and then redaction.
+    pub fn map(
+        &self,
+        record: &mut Record,
+        source: &str,
+        recording: Recording,
+    ) -> Result<(), Error> {
+        mapping::apply(record, self.mapping.rules_for(source))?;
+        self.redactor.apply(record, source, recording)
+    }
+}
+
+/// The active pipeline, swapped as a whole on reload so an object is always
+/// processed with one consistent configuration.
+pub struct SharedPipeline {
+    current: RwLock<Arc<Pipeline>>,
+}
+
+impl SharedPipeline {
+    pub fn new(pipeline: Pipeline) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(pipeline)),
+        }
+    }
+
+    pub fn current(&self) -> Arc<Pipeline> {
+        self.current.read().unwrap().clone()
+    }
+
+    pub fn replace(&self, pipeline: Pipeline) {
+        *self.current.write().unwrap() = Arc::new(pipeline);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn suffixes_answers_named_like_the_audit_field() {
+        let config = serde_json::from_value(json!({
+            "redaction": {"detectors": [{"detect": "email", "action": "mask"}]},
+        }))
+        .unwrap();
+        let pipeline = Pipeline::new(&config, None).unwrap();
+        let event = |email: &str| -> Event {
+            serde_json::from_value(json!({
+                "request": {"source": "somewhere", "answers": {"contact": email, "redactions": "none"}},
+                "response": {"id": "1234"},
+            }))
+            .unwrap()
+        };
+
+        for email in ["a@example.com", "not given"] {
+            let mut record = pipeline
+                .flatten(&event(email), &notification(), Recording::On)
+                .unwrap();
+            pipeline
+                .map(&mut record, "somewhere", Recording::On)
+                .unwrap();
+            assert_eq!(record["redactions_2"], json!("none"));
+        }
+    }
+
+    fn notification() -> Notification {
+        Notification::builder()
+            .message_id("some-message")
+            .receipt_handle("receipt")
+            .created(Utc::now())
+            .bucket("test-bucket")
+            .key("1234.json")
+            .build()
+    }
+}
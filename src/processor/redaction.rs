@@ -0,0 +1,387 @@
+use std::sync::LazyLock;
+
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::Sha256;
+
+use crate::{config::RedactionConfig, metrics};
+
+use super::{Error, Recording};
+
+static EMAIL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[^@\s]+@[^@\s]+\.[^@\s.]+$").unwrap());
+static PHONE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\+?[0-9 ().-]+$").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Drop,
+    Mask,
+    Hash,
+}
+
+/// Redacts fields whose name matches a glob pattern, where `*` matches any
+/// run of characters and `?` matches exactly one.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FieldRule {
+    pattern: String,
+    action: Action,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Detect {
+    Email,
+    Phone,
+}
+
+impl Detect {
+    fn matches(&self, text: &str) -> bool {
+        let text = text.trim();
+        match self {
+            Detect::Email => EMAIL.is_match(text),
+            // Digit count rules out dates and short numeric answers.
+            Detect::Phone => {
+                let digits = text.chars().filter(char::is_ascii_digit).count();
+                PHONE.is_match(text) && (10..=15).contains(&digits)
+            }
+        }
+    }
+}
+
+/// Redacts fields whose whole value is an email address or phone number.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DetectorRule {
+    detect: Detect,
+    action: Action,
+}
+
+/// A rule that fired on a record, kept in the record for audit.
+#[derive(Debug, Serialize)]
+struct Redaction {
+    field: String,
+    rule: String,
+    action: Action,
+}
+
+/// Only answers are redacted. The metadata fields named by `reserved` are left
+/// alone, so e.g. a numeric event id is never taken for a phone number.
+pub struct Redactor {
+    config: RedactionConfig,
+    reserved: Vec<String>,
+    key: Option<Vec<u8>>,
+}
+
+impl Redactor {
+    pub fn new(
+        config: RedactionConfig,
+        reserved: Vec<String>,
+        key: Option<Vec<u8>>,
+    ) -> Result<Self, String> {
+        let hashes = config
+            .fields()
+            .iter()
+            .any(|rule| rule.action == Action::Hash)
+            || config
+                .detectors()
+                .iter()
+                .any(|rule| rule.action == Action::Hash);
+        if key.is_none() && hashes {
+            return Err(String::from(
+                "REDACTION_HMAC_KEY should be provided to hash fields",
+            ));
+        }
+        Ok(Self {
+            config,
+            reserved,
+            key,
+        })
+    }
+
+    /// Field rules are checked in order and the first match wins; detectors
+    /// only look at fields that no field rule matched. The audit field is only
+    /// added when something was redacted.
+    pub fn apply(
+        &self,
+        record: &mut Map<String, Value>,
+        source: &str,
+        recording: Recording,
+    ) -> Result<(), Error> {
+        if self.config.fields().is_empty() && self.config.detectors().is_empty() {
+            return Ok(());
+        }
+
+        let mut redactions = Vec::new();
+        let fields: Vec<String> = record
+            .keys()
+            .filter(|field| !self.reserved.contains(field))
+            .cloned()
+            .collect();
+        for field in fields {
+            let Some(text) = record.get(&field).and_then(text) else {
+                continue;
+            };
+            let Some((rule, action)) = self.rule_for(&field, &text) else {
+                continue;
+            };
+
+            match action {
+                Action::Drop => {
+                    record.remove(&field);
+                }
+                Action::Mask => {
+                    record.insert(field.clone(), Value::String(mask(&text)));
+                }
+                Action::Hash => {
+                    record.insert(field.clone(), Value::String(self.hash(&text)));
+                }
+            }
+            if recording == Recording::On {
+                metrics::increment(
+                    "redactions_total",
+                    &[("source", source), ("action", action_name(action))],
+                );
+            }
+            redactions.push(Redaction {
+                field,
+                rule,
+                action,
+            });
+        }
+
+        if redactions.is_empty() {
+            return Ok(());
+        }
+        let audit_field = self.config.audit_field();
+        if record.contains_key(audit_field) {
+            return Err(Error::ReservedCollision {
+                key: String::from(audit_field),
+            });
+        }
+        record.insert(
+            String::from(audit_field),
+            serde_json::to_value(redactions).unwrap(),
+        );
+        Ok(())
+    }
+
+    fn rule_for(&self, field: &str, text: &str) -> Option<(String, Action)> {
+        if let Some(rule) = self
+            .config
+            .fields()
+            .iter()
+            .find(|rule| glob(&rule.pattern, field))
+        {
+            return Some((rule.pattern.clone(), rule.action));
+        }
+
+        self.config
+            .detectors()
+            .iter()
+            .find(|rule| rule.detect.matches(text))
+            .map(|rule| (detector_name(rule.detect).to_owned(), rule.action))
+    }
+
+    fn hash(&self, text: &str) -> String {
+        // The key is checked on construction whenever a rule hashes.
+        let key = self.key.as_deref().unwrap_or_default();
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+        mac.update(text.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+}
+
+// Nulls have nothing to redact, so no rule fires on them.
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(text) => Some(text.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+fn mask(text: &str) -> String {
+    "*".repeat(text.chars().count())
+}
+
+fn action_name(action: Action) -> &'static str {
+    match action {
+        Action::Drop => "drop",
+        Action::Mask => "mask",
+        Action::Hash => "hash",
+    }
+}
+
+fn detector_name(detect: Detect) -> &'static str {
+    match detect {
+        Detect::Email => "email",
+        Detect::Phone => "phone",
+    }
+}
+
+fn glob(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where to resume after the last `*` if the current attempt fails.
+    let mut backtrack = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn matches_glob_patterns() {
+        assert!(glob("*_email", "contact_email"));
+        assert!(glob("pets*_name", "pets_1_name"));
+        assert!(glob("pets*_name", "pets.0.owner_name"));
+        assert!(glob("phone?", "phone2"));
+        assert!(!glob("*_email", "email_verified"));
+        assert!(!glob("pets*_name", "pets_1_age"));
+    }
+
+    #[test]
+    fn detects_emails_and_phone_numbers() {
+        assert!(Detect::Email.matches("tim@example.com"));
+        assert!(!Detect::Email.matches("tim at example"));
+        assert!(Detect::Phone.matches("+44 (0)20 7946 0958"));
+        assert!(!Detect::Phone.matches("2024-01-01"));
+    }
+
+    #[test]
+    fn redacts_matching_fields_and_records_audit() {
+        let redactor = redactor(json!({
+            "fields": [
+                {"pattern": "*_email", "action": "hash"},
+                {"pattern": "pets*_name", "action": "drop"},
+                {"pattern": "last_name", "action": "mask"},
+            ],
+            "detectors": [{"detect": "phone", "action": "mask"}],
+        }));
+        let mut record = record(json!({
+            "id": "1234",
+            "contact_email": "tim@example.com",
+            "pets_1_name": "Rex",
+            "last_name": "Smith",
+            "notes": "07700 900123",
+            "age": 42,
+        }));
+
+        redactor
+            .apply(&mut record, "somewhere", Recording::On)
+            .unwrap();
+
+        assert_eq!(record["contact_email"].as_str().unwrap().len(), 64);
+        assert!(!record.contains_key("pets_1_name"));
+        assert_eq!(record["last_name"], json!("*****"));
+        assert_eq!(record["notes"], json!("************"));
+        assert_eq!(record["age"], json!(42));
+        assert_eq!(
+            record["redactions"],
+            json!([
+                {"field": "contact_email", "rule": "*_email", "action": "hash"},
+                {"field": "last_name", "rule": "last_name", "action": "mask"},
+                {"field": "notes", "rule": "phone", "action": "mask"},
+                {"field": "pets_1_name", "rule": "pets*_name", "action": "drop"},
+            ])
+        );
+    }
+
+    #[test]
+    fn leaves_metadata_and_unredacted_records_alone() {
+        let redactor = redactor(json!({
+            "fields": [{"pattern": "*id", "action": "drop"}],
+            "detectors": [{"detect": "phone", "action": "mask"}],
+        }));
+        let mut record = record(json!({
+            "id": 123456789012u64,
+            "created": "2024-08-01T12:00:00+00:00",
+            "age": 42,
+        }));
+
+        redactor
+            .apply(&mut record, "somewhere", Recording::On)
+            .unwrap();
+
+        assert_eq!(
+            Value::Object(record),
+            json!({"id": 123456789012u64, "created": "2024-08-01T12:00:00+00:00", "age": 42})
+        );
+    }
+
+    #[test]
+    fn hashes_consistently_with_key() {
+        let redactor = redactor(json!({"fields": [{"pattern": "email", "action": "hash"}]}));
+        let mut first = record(json!({"email": "tim@example.com"}));
+        let mut second = record(json!({"email": "tim@example.com"}));
+
+        redactor
+            .apply(&mut first, "somewhere", Recording::On)
+            .unwrap();
+        redactor
+            .apply(&mut second, "somewhere", Recording::On)
+            .unwrap();
+
+        assert_eq!(first["email"], second["email"]);
+        assert_ne!(first["email"], json!("tim@example.com"));
+    }
+
+    #[test]
+    fn leaves_record_alone_without_rules() {
+        let redactor = redactor(json!({}));
+        let mut record = record(json!({"email": "tim@example.com"}));
+
+        redactor
+            .apply(&mut record, "somewhere", Recording::On)
+            .unwrap();
+
+        assert_eq!(Value::Object(record), json!({"email": "tim@example.com"}));
+    }
+
+    #[test]
+    fn requires_key_to_hash() {
+        let config =
+            serde_json::from_value(json!({"fields": [{"pattern": "email", "action": "hash"}]}))
+                .unwrap();
+        assert!(Redactor::new(config, Vec::new(), None).is_err());
+    }
+
+    fn redactor(config: Value) -> Redactor {
+        Redactor::new(
+            serde_json::from_value(config).unwrap(),
+            vec![
+                String::from("id"),
+                String::from("created"),
+                String::from("s3_uri"),
+            ],
+            Some(b"secret".to_vec()),
+        )
+        .unwrap()
+    }
+
+    fn record(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().to_owned()
+    }
+}
@@ -1,88 +1,625 @@
-use std::collections::HashMap;
+use std::{
+    collections::BTreeMap,
+    sync::{LazyLock, Mutex},
+};
 
-use crate::model::{Answer, Event, Notification};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use utoipa::ToSchema;
 
-pub fn apply(event: &Event, notification: &Notification) -> HashMap<String, String> {
-    let mut map = HashMap::new();
-    map.insert(String::from("id"), String::from(event.response().id()));
-    map.insert(String::from("created"), notification.created().to_rfc3339());
-    map.insert(
+use crate::{
+    config::TransformConfig,
+    metrics,
+    model::{Event, Notification},
+};
+
+use super::{Error, Recording};
+
+/// How the path to a nested value is written into its flattened key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyScheme {
+    /// `pets1_name`, with 1-based indexes appended to the parent key.
+    #[default]
+    Indexed,
+    /// `pets.0.name`
+    Dotted,
+    /// `pets[0].name`
+    Bracketed,
+}
+
+impl KeyScheme {
+    fn child(&self, parent: &str, child: &str) -> String {
+        match self {
+            KeyScheme::Indexed => format!("{}_{}", parent, child),
+            KeyScheme::Dotted | KeyScheme::Bracketed => format!("{}.{}", parent, child),
+        }
+    }
+
+    fn index(&self, parent: &str, index: usize) -> String {
+        match self {
+            KeyScheme::Indexed => format!("{}{}", parent, index + 1),
+            KeyScheme::Dotted => format!("{}.{}", parent, index),
+            KeyScheme::Bracketed => format!("{}[{}]", parent, index),
+        }
+    }
+}
+
+/// What to do when two answers flatten to the same key, e.g. an answer
+/// literally named `pets1_name` alongside a `pets` collection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionPolicy {
+    Error,
+    /// Keeps both values, renaming the later one to `{key}_2`, `{key}_3`...
+    #[default]
+    Suffix,
+    Overwrite,
+}
+
+/// Where the metadata fields (`id`, `created` and `s3_uri`) are written so they
+/// can be told apart from answers.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReservedFields {
+    /// Top-level fields with the given prefix, e.g. `_id` for `_`.
+    Prefix(String),
+    /// A single top-level object holding every metadata field.
+    Namespace(String),
+}
+
+impl Default for ReservedFields {
+    fn default() -> Self {
+        ReservedFields::Prefix(String::new())
+    }
+}
+
+impl ReservedFields {
+    /// The top-level record keys that hold metadata.
+    pub fn names(&self) -> Vec<String> {
+        let metadata = METADATA_FIELDS
+            .iter()
+            .map(|field| (String::from(*field), Value::Null))
+            .collect();
+        self.place(metadata)
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    fn place(&self, metadata: Map<String, Value>) -> Map<String, Value> {
+        match self {
+            ReservedFields::Prefix(prefix) => metadata
+                .into_iter()
+                .map(|(key, value)| (format!("{}{}", prefix, key), value))
+                .collect(),
+            ReservedFields::Namespace(namespace) => {
+                Map::from_iter([(namespace.clone(), Value::Object(metadata))])
+            }
+        }
+    }
+}
+
+/// How often answers from a source have collided with another field.
+#[derive(Debug, PartialEq, Eq, Serialize, ToSchema)]
+pub struct CollisionCount {
+    source: String,
+    field: String,
+    reserved: bool,
+    count: u64,
+}
+
+pub fn collision_counts() -> Vec<CollisionCount> {
+    COLLISIONS.lock().unwrap().counts()
+}
+
+const METADATA_FIELDS: [&str; 3] = ["id", "created", "s3_uri"];
+
+const ANSWER_COLLISIONS: &str = "answer_key_collisions_total";
+const RESERVED_COLLISIONS: &str = "reserved_field_collisions_total";
+
+/// Answer names are user supplied, so only this many source and field pairs are
+/// counted separately. Any later pair is counted under `OTHER`.
+const MAX_COLLISION_FIELDS: usize = 1000;
+const OTHER: &str = "*";
+
+static COLLISIONS: LazyLock<Mutex<Collisions>> =
+    LazyLock::new(|| Mutex::new(Collisions::new(MAX_COLLISION_FIELDS)));
+
+/// Per field collision counts for `/collisions`, kept out of the metrics
+/// registry so the exported series stay bounded.
+struct Collisions {
+    max: usize,
+    counts: BTreeMap<(String, String, bool), u64>,
+}
+
+impl Collisions {
+    fn new(max: usize) -> Self {
+        Self {
+            max,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    fn record(&mut self, source: &str, field: &str, reserved: bool) {
+        let key = (String::from(source), String::from(field), reserved);
+        let key = if self.counts.contains_key(&key) || self.counts.len() < self.max {
+            key
+        } else {
+            (String::from(OTHER), String::from(OTHER), reserved)
+        };
+        *self.counts.entry(key).or_default() += 1;
+    }
+
+    fn counts(&self) -> Vec<CollisionCount> {
+        self.counts
+            .iter()
+            .map(|((source, field, reserved), count)| CollisionCount {
+                source: source.clone(),
+                field: field.clone(),
+                reserved: *reserved,
+                count: *count,
+            })
+            .collect()
+    }
+}
+
+fn record_collision(source: &str, field: &str, reserved: bool, recording: Recording) {
+    if recording == Recording::Off {
+        return;
+    }
+    let name = if reserved {
+        RESERVED_COLLISIONS
+    } else {
+        ANSWER_COLLISIONS
+    };
+    metrics::increment(name, &[("source", source)]);
+    COLLISIONS.lock().unwrap().record(source, field, reserved);
+}
+
+/// Answers may not take the metadata fields or any other `reserved` key, such
+/// as the redaction audit field.
+pub fn apply(
+    event: &Event,
+    notification: &Notification,
+    config: &TransformConfig,
+    reserved: &[String],
+    recording: Recording,
+) -> Result<Map<String, Value>, Error> {
+    let mut metadata = Map::new();
+    metadata.insert(String::from("id"), Value::from(event.response().id()));
+    metadata.insert(
+        String::from("created"),
+        Value::from(notification.created().to_rfc3339()),
+    );
+    metadata.insert(
         String::from("s3_uri"),
-        format!("s3://{}/{}", notification.bucket(), notification.key()),
+        Value::from(format!(
+            "s3://{}/{}",
+            notification.bucket(),
+            notification.key()
+        )),
     );
 
-    let answers: HashMap<String, String> = event
-        .request()
-        .answers()
-        .iter()
-        .flat_map(|(key, answer)| transform_answer(key, answer))
-        .collect();
+    let mut map = config.reserved_fields().place(metadata);
+    let reserved: Vec<String> = map.keys().chain(reserved).cloned().collect();
+
+    // Sorted so collisions resolve the same way every time.
+    let mut answers: Vec<_> = event.request().answers().iter().collect();
+    answers.sort_by_key(|(key, _)| *key);
 
-    map.extend(answers);
+    for (key, answer) in answers {
+        let mut values = Vec::new();
+        flatten(key, answer.value(), config.key_scheme(), &mut values);
+
+        for (key, value) in values {
+            let value = if config.stringify_values() {
+                stringified(value)
+            } else {
+                value
+            };
+            let source = event.request().source();
+            if reserved.contains(&key) {
+                insert_reserved(&mut map, key, value, source, config, recording)?;
+            } else {
+                insert(&mut map, key, value, source, config, recording)?;
+            }
+        }
+    }
 
-    map
+    Ok(map)
 }
 
-fn transform_answer(key: &str, answer: &Answer) -> Vec<(String, String)> {
-    match answer {
-        Answer::Simple(value) => vec![(String::from(key), String::from(value))],
-        Answer::Collection(collection) => collection
-            .iter()
-            .enumerate()
-            .flat_map(|(idx, value)| transform_collection_answer(key, idx, value))
-            .collect(),
+// Empty containers have nothing to flatten, so they are kept whole rather than
+// dropping the answer.
+fn flatten(key: &str, value: &Value, scheme: KeyScheme, values: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Array(items) if !items.is_empty() => {
+            for (idx, item) in items.iter().enumerate() {
+                flatten(&scheme.index(key, idx), item, scheme, values);
+            }
+        }
+        Value::Object(children) if !children.is_empty() => {
+            for (child_key, child) in children {
+                flatten(&scheme.child(key, child_key), child, scheme, values);
+            }
+        }
+        other => values.push((String::from(key), other.clone())),
     }
 }
 
-fn transform_collection_answer(
-    parent_key: &str,
-    index: usize,
-    value: &HashMap<String, String>,
-) -> Vec<(String, String)> {
-    value
-        .iter()
-        .map(|(child_key, child_value)| {
-            (
-                format!("{}{}_{}", parent_key, index + 1, child_key),
-                child_value.to_owned(),
-            )
-        })
-        .collect()
+fn insert(
+    map: &mut Map<String, Value>,
+    key: String,
+    value: Value,
+    source: &str,
+    config: &TransformConfig,
+    recording: Recording,
+) -> Result<(), Error> {
+    if !map.contains_key(&key) {
+        map.insert(key, value);
+        return Ok(());
+    }
+
+    tracing::warn!("Answer key '{}' from source '{}' collides", key, source);
+    record_collision(source, &key, false, recording);
+
+    match config.collision_policy() {
+        CollisionPolicy::Error => Err(Error::Collision { key }),
+        CollisionPolicy::Suffix => {
+            insert_suffixed(map, &key, value);
+            Ok(())
+        }
+        CollisionPolicy::Overwrite => {
+            map.insert(key, value);
+            Ok(())
+        }
+    }
+}
+
+// Metadata is never overwritten, so the overwrite policy suffixes the answer.
+fn insert_reserved(
+    map: &mut Map<String, Value>,
+    key: String,
+    value: Value,
+    source: &str,
+    config: &TransformConfig,
+    recording: Recording,
+) -> Result<(), Error> {
+    tracing::warn!(
+        "Answer key '{}' from source '{}' collides with a reserved field",
+        key,
+        source
+    );
+    record_collision(source, &key, true, recording);
+
+    match config.collision_policy() {
+        CollisionPolicy::Error => Err(Error::ReservedCollision { key }),
+        CollisionPolicy::Suffix | CollisionPolicy::Overwrite => {
+            insert_suffixed(map, &key, value);
+            Ok(())
+        }
+    }
+}
+
+fn insert_suffixed(map: &mut Map<String, Value>, key: &str, value: Value) {
+    let suffixed = (2..)
+        .map(|n| format!("{}_{}", key, n))
+        .find(|candidate| !map.contains_key(candidate))
+        .unwrap();
+    map.insert(suffixed, value);
+}
+
+// Compatibility mode for consumers that expect every value to be a string.
+fn stringified(value: Value) -> Value {
+    match value {
+        Value::String(_) => value,
+        other => Value::String(other.to_string()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::str::FromStr;
+    use std::{collections::HashMap, str::FromStr};
 
     use chrono::DateTime;
+    use serde_json::json;
 
-    use crate::model::{Request, Response};
+    use crate::model::{Answer, Request, Response};
 
     use super::*;
 
     #[test]
     fn transforms_event() {
-        let actual = apply(&event(), &notification());
+        let actual = apply(
+            &event(),
+            &notification(),
+            &TransformConfig::default(),
+            &[],
+            Recording::On,
+        )
+        .unwrap();
         assert_eq!(actual, expected())
     }
 
-    fn expected() -> HashMap<String, String> {
-        HashMap::from([
-            (String::from("id"), String::from("1234")),
-            (
-                String::from("created"),
-                String::from("2024-08-10T11:00:00+00:00"),
+    #[test]
+    fn stringifies_values_when_configured() {
+        let actual = apply(
+            &event(),
+            &notification(),
+            &config(r#"{"stringify_values":true}"#),
+            &[],
+            Recording::On,
+        )
+        .unwrap();
+        assert_eq!(actual["age"], json!("42"));
+        assert_eq!(actual["pets1_vaccinated"], json!("true"));
+        assert_eq!(actual["nickname"], json!("null"));
+        assert_eq!(actual["first_name"], json!("Tim"));
+    }
+
+    #[test]
+    fn flattens_nested_answers_with_indexed_keys() {
+        let actual = flattened_pets(r#"{"key_scheme":"indexed"}"#);
+        assert_eq!(actual["pets1_vaccinations2_date"], json!("2024-02-01"));
+        assert_eq!(actual["pets1_owner_name"], json!("Tim"));
+    }
+
+    #[test]
+    fn keeps_empty_containers() {
+        let answers = HashMap::from([
+            (String::from("tags"), Answer::new(json!([]))),
+            (String::from("address"), Answer::new(json!({"lines": []}))),
+            (String::from("extra"), Answer::new(json!({}))),
+        ]);
+        let event = Event::new(Request::new("somewhere", answers), Response::new("1"));
+
+        let actual = apply(
+            &event,
+            &notification(),
+            &TransformConfig::default(),
+            &[],
+            Recording::On,
+        )
+        .unwrap();
+
+        assert_eq!(actual["tags"], json!([]));
+        assert_eq!(actual["address_lines"], json!([]));
+        assert_eq!(actual["extra"], json!({}));
+    }
+
+    #[test]
+    fn flattens_nested_answers_with_dotted_keys() {
+        let actual = flattened_pets(r#"{"key_scheme":"dotted"}"#);
+        assert_eq!(actual["pets.0.vaccinations.1.date"], json!("2024-02-01"));
+        assert_eq!(actual["pets.0.owner.name"], json!("Tim"));
+    }
+
+    #[test]
+    fn flattens_nested_answers_with_bracketed_keys() {
+        let actual = flattened_pets(r#"{"key_scheme":"bracketed"}"#);
+        assert_eq!(actual["pets[0].vaccinations[1].date"], json!("2024-02-01"));
+        assert_eq!(actual["pets[0].owner.name"], json!("Tim"));
+    }
+
+    #[test]
+    fn rejects_collision_when_configured() {
+        let actual = apply(
+            &colliding_event(),
+            &notification(),
+            &config(r#"{"collision_policy":"error"}"#),
+            &[],
+            Recording::On,
+        );
+        assert!(matches!(actual, Err(Error::Collision { key }) if key == "pets1_name"))
+    }
+
+    #[test]
+    fn suffixes_colliding_key_when_configured() {
+        let actual = apply(
+            &colliding_event(),
+            &notification(),
+            &config(r#"{"collision_policy":"suffix"}"#),
+            &[],
+            Recording::On,
+        )
+        .unwrap();
+        assert_eq!(actual["pets1_name"], json!("Tiffin"));
+        assert_eq!(actual["pets1_name_2"], json!("Literal"));
+    }
+
+    #[test]
+    fn overwrites_colliding_key_when_configured() {
+        let actual = apply(
+            &colliding_event(),
+            &notification(),
+            &config(r#"{"collision_policy":"overwrite"}"#),
+            &[],
+            Recording::On,
+        )
+        .unwrap();
+        assert_eq!(actual["pets1_name"], json!("Literal"));
+    }
+
+    #[test]
+    fn prefixes_reserved_fields_when_configured() {
+        let actual = apply(
+            &event(),
+            &notification(),
+            &config(r#"{"reserved_fields":{"prefix":"_"}}"#),
+            &[],
+            Recording::On,
+        )
+        .unwrap();
+        assert_eq!(actual["_id"], json!("1234"));
+        assert!(!actual.contains_key("id"));
+    }
+
+    #[test]
+    fn namespaces_reserved_fields_when_configured() {
+        let actual = apply(
+            &event(),
+            &notification(),
+            &config(r#"{"reserved_fields":{"namespace":"_meta"}}"#),
+            &[],
+            Recording::On,
+        )
+        .unwrap();
+        assert_eq!(actual["_meta"]["id"], json!("1234"));
+        assert_eq!(
+            actual["_meta"]["s3_uri"],
+            json!("s3://test-bucket/1234.json")
+        );
+    }
+
+    #[test]
+    fn never_overwrites_reserved_fields() {
+        let actual = apply(
+            &answering_event("id", json!("answer")),
+            &notification(),
+            &config(r#"{"collision_policy":"overwrite"}"#),
+            &[],
+            Recording::On,
+        )
+        .unwrap();
+        assert_eq!(actual["id"], json!("1234"));
+        assert_eq!(actual["id_2"], json!("answer"));
+    }
+
+    #[test]
+    fn rejects_reserved_collision_when_configured() {
+        let actual = apply(
+            &answering_event("created", json!("yesterday")),
+            &notification(),
+            &config(r#"{"collision_policy":"error"}"#),
+            &[],
+            Recording::On,
+        );
+        assert!(matches!(actual, Err(Error::ReservedCollision { key }) if key == "created"))
+    }
+
+    #[test]
+    fn counts_reserved_collisions_per_source() {
+        let event = Event::new(
+            Request::new(
+                "collision-counting-source",
+                HashMap::from([(String::from("s3_uri"), Answer::new(json!("s3://elsewhere")))]),
             ),
-            (
-                String::from("s3_uri"),
-                String::from("s3://test-bucket/1234.json"),
+            response(),
+        );
+        apply(
+            &event,
+            &notification(),
+            &TransformConfig::default(),
+            &[],
+            Recording::On,
+        )
+        .unwrap();
+
+        let actual = collision_counts();
+        assert!(actual.contains(&CollisionCount {
+            source: String::from("collision-counting-source"),
+            field: String::from("s3_uri"),
+            reserved: true,
+            count: 1,
+        }));
+    }
+
+    #[test]
+    fn bounds_fields_counted_separately() {
+        let mut collisions = Collisions::new(2);
+        collisions.record("somewhere", "a", false);
+        collisions.record("somewhere", "b", false);
+        collisions.record("somewhere", "c", false);
+        collisions.record("elsewhere", "d", false);
+        collisions.record("somewhere", "a", false);
+
+        let actual: Vec<_> = collisions
+            .counts()
+            .into_iter()
+            .map(|count| (count.source, count.field, count.count))
+            .collect();
+        assert_eq!(
+            actual,
+            vec![
+                (String::from("*"), String::from("*"), 2),
+                (String::from("somewhere"), String::from("a"), 2),
+                (String::from("somewhere"), String::from("b"), 1),
+            ]
+        );
+    }
+
+    fn answering_event(key: &str, value: Value) -> Event {
+        Event::new(
+            Request::new(
+                "somewhere",
+                HashMap::from([(String::from(key), Answer::new(value))]),
             ),
-            (String::from("first_name"), String::from("Tim")),
-            (String::from("pets1_type"), String::from("Cat")),
-            (String::from("pets1_name"), String::from("Tiffin")),
-            (String::from("pets2_type"), String::from("Dog")),
-            (String::from("pets2_name"), String::from("Waldo")),
-        ])
+            response(),
+        )
+    }
+
+    fn config(json: &str) -> TransformConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn flattened_pets(config_json: &str) -> Map<String, Value> {
+        let event = Event::new(
+            Request::new(
+                "somewhere",
+                HashMap::from([(
+                    String::from("pets"),
+                    Answer::new(json!([{
+                        "name": "Tiffin",
+                        "owner": {"name": "Tim"},
+                        "vaccinations": [{"date": "2024-01-01"}, {"date": "2024-02-01"}],
+                    }])),
+                )]),
+            ),
+            response(),
+        );
+        apply(
+            &event,
+            &notification(),
+            &config(config_json),
+            &[],
+            Recording::On,
+        )
+        .unwrap()
+    }
+
+    fn colliding_event() -> Event {
+        Event::new(
+            Request::new(
+                "somewhere",
+                HashMap::from([
+                    (
+                        String::from("pets"),
+                        Answer::new(json!([{"name": "Tiffin"}])),
+                    ),
+                    (String::from("pets1_name"), Answer::new(json!("Literal"))),
+                ]),
+            ),
+            response(),
+        )
+    }
+
+    fn expected() -> Map<String, Value> {
+        let expected = json!({
+            "id": "1234",
+            "created": "2024-08-10T11:00:00+00:00",
+            "s3_uri": "s3://test-bucket/1234.json",
+            "first_name": "Tim",
+            "age": 42,
+            "nickname": null,
+            "pets1_type": "Cat",
+            "pets1_name": "Tiffin",
+            "pets1_vaccinated": true,
+            "pets2_type": "Dog",
+            "pets2_name": "Waldo",
+            "pets2_vaccinated": false,
+        });
+        expected.as_object().unwrap().to_owned()
     }
 
     fn notification() -> Notification {
@@ -103,22 +640,15 @@ mod tests {
         Request::new(
             "somewhere",
             HashMap::from([
-                (
-                    String::from("first_name"),
-                    Answer::Simple(String::from("Tim")),
-                ),
+                (String::from("first_name"), Answer::new(json!("Tim"))),
+                (String::from("age"), Answer::new(json!(42))),
+                (String::from("nickname"), Answer::new(json!(null))),
                 (
                     String::from("pets"),
-                    Answer::Collection(vec![
-                        HashMap::from([
-                            (String::from("type"), String::from("Cat")),
-                            (String::from("name"), String::from("Tiffin")),
-                        ]),
-                        HashMap::from([
-                            (String::from("type"), String::from("Dog")),
-                            (String::from("name"), String::from("Waldo")),
-                        ]),
-                    ]),
+                    Answer::new(json!([
+                        {"type": "Cat", "name": "Tiffin", "vaccinated": true},
+                        {"type": "Dog", "name": "Waldo", "vaccinated": false},
+                    ])),
                 ),
             ]),
         )
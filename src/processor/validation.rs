@@ -0,0 +1,177 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use jsonschema::JSONSchema;
+use serde::Serialize;
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use crate::{metrics, model::Request};
+
+use super::Error;
+
+/// A loaded schema as listed by the `/schemas` endpoint.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SchemaInfo {
+    source: String,
+    version: Option<String>,
+    file: String,
+}
+
+struct Schema {
+    info: SchemaInfo,
+    compiled: JSONSchema,
+}
+
+/// JSON Schemas for event requests, one per source. Sources without a schema
+/// are not validated.
+#[derive(Default)]
+pub struct Schemas {
+    schemas: BTreeMap<String, Schema>,
+}
+
+impl Schemas {
+    /// Loads every `<source>.json` file in the directory. A schema that cannot
+    /// be read or compiled stops startup rather than letting events through.
+    pub fn load(directory: &Path) -> Self {
+        let mut schemas = BTreeMap::new();
+        let entries = fs::read_dir(directory).expect("Schema directory should be readable");
+
+        for entry in entries {
+            let path = entry.expect("Schema directory should be readable").path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(source) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+
+            let content = fs::read_to_string(&path).expect("Schema file should be readable");
+            let schema: Value = serde_json::from_str(&content).expect("Schema should be JSON");
+            tracing::info!("Loaded schema for source '{}'", source);
+            schemas.insert(
+                String::from(source),
+                Schema::new(source, &path.display().to_string(), &schema),
+            );
+        }
+        Self { schemas }
+    }
+
+    pub fn list(&self) -> Vec<SchemaInfo> {
+        self.schemas
+            .values()
+            .map(|schema| schema.info.clone())
+            .collect()
+    }
+
+    pub fn validate(&self, request: &Request) -> Result<(), Error> {
+        let source = request.source();
+        let Some(schema) = self.schemas.get(source) else {
+            return Ok(());
+        };
+
+        let instance = serde_json::to_value(request).unwrap();
+        let result = schema.compiled.validate(&instance).map_err(|errors| {
+            errors
+                .map(|e| format!("{} at '{}'", e, e.instance_path))
+                .collect::<Vec<String>>()
+        });
+        let outcome = if result.is_ok() { "valid" } else { "invalid" };
+        metrics::increment(
+            "schema_validations_total",
+            &[("source", source), ("result", outcome)],
+        );
+
+        result.map_err(|errors| Error::Schema {
+            source: String::from(source),
+            errors,
+        })
+    }
+}
+
+impl Schema {
+    fn new(source: &str, file: &str, schema: &Value) -> Self {
+        // `version` is not a schema keyword, so validators ignore it.
+        let version = schema
+            .get("version")
+            .or_else(|| schema.get("$id"))
+            .and_then(Value::as_str)
+            .map(String::from);
+        let compiled = JSONSchema::compile(schema).expect("Schema should be valid");
+
+        Self {
+            info: SchemaInfo {
+                source: String::from(source),
+                version,
+                file: String::from(file),
+            },
+            compiled,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn accepts_valid_request() {
+        let schemas = schemas();
+        assert!(schemas.validate(&request(json!({"age": 42}))).is_ok())
+    }
+
+    #[test]
+    fn lists_schema_errors() {
+        let schemas = schemas();
+
+        let actual = schemas
+            .validate(&request(json!({"age": "forty"})))
+            .unwrap_err();
+
+        match actual {
+            Error::Schema { source, errors } => {
+                assert_eq!(source, "somewhere");
+                assert_eq!(errors.len(), 1);
+                assert!(errors[0].ends_with("at '/answers/age'"));
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn skips_sources_without_schema() {
+        let schemas = Schemas::default();
+        assert!(schemas.validate(&request(json!({"age": "forty"}))).is_ok())
+    }
+
+    #[test]
+    fn reads_version() {
+        let actual = schemas().list();
+        assert_eq!(actual[0].version.as_deref(), Some("2"));
+    }
+
+    fn schemas() -> Schemas {
+        let schema = json!({
+            "version": "2",
+            "type": "object",
+            "properties": {
+                "answers": {
+                    "type": "object",
+                    "properties": {"age": {"type": "integer"}},
+                    "required": ["age"],
+                },
+            },
+        });
+        let mut schemas = BTreeMap::new();
+        schemas.insert(
+            String::from("somewhere"),
+            Schema::new("somewhere", "somewhere.json", &schema),
+        );
+        Schemas { schemas }
+    }
+
+    fn request(answers: Value) -> Request {
+        serde_json::from_value(json!({"source": "somewhere", "answers": answers})).unwrap()
+    }
+}
@@ -0,0 +1,300 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+
+use crate::{
+    config::Config,
+    metrics,
+    processor::{Pipeline, SharedPipeline},
+};
+
+const REDACTED: &str = "***";
+
+/// The sections a reload applies to the running pipeline. Changes to any
+/// other section only take effect on restart.
+const APPLIED: [&str; 3] = ["transform", "mapping", "redaction"];
+
+/// The active configuration as reported over HTTP, with secrets hidden.
+/// `config` holds only the applied sections, and `restart_required` names
+/// the other sections that have changed in the file since startup.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ConfigView {
+    version: u64,
+    digest: Option<String>,
+    loaded: DateTime<Utc>,
+    config: Value,
+    restart_required: Vec<String>,
+}
+
+struct Active {
+    view: ConfigView,
+    modified: Option<SystemTime>,
+}
+
+/// Reloads the config file into the shared pipeline. A file that fails to
+/// parse or validate is reported and the running configuration is kept.
+pub struct Reloader {
+    path: Option<PathBuf>,
+    key: Option<Vec<u8>>,
+    pipeline: Arc<SharedPipeline>,
+    started: Map<String, Value>,
+    active: Mutex<Active>,
+}
+
+impl Reloader {
+    pub fn new(path: Option<PathBuf>, key: Option<Vec<u8>>, pipeline: Arc<SharedPipeline>) -> Self {
+        let content = path
+            .as_deref()
+            .and_then(|path| fs::read_to_string(path).ok());
+        let started = content
+            .as_deref()
+            .and_then(|content| serde_json::from_str(content).ok())
+            .unwrap_or_default();
+        let config = applied(&started);
+        let active = Active {
+            view: ConfigView {
+                version: 1,
+                digest: content.map(|_| digest(&config)),
+                loaded: Utc::now(),
+                config: redact(config),
+                restart_required: Vec::new(),
+            },
+            modified: path.as_deref().and_then(modified),
+        };
+
+        Self {
+            path,
+            key,
+            pipeline,
+            started,
+            active: Mutex::new(active),
+        }
+    }
+
+    pub fn view(&self) -> ConfigView {
+        self.active.lock().unwrap().view.clone()
+    }
+
+    pub fn reload(&self) -> Result<ConfigView, String> {
+        let result = self.try_reload();
+        match &result {
+            Ok(view) => {
+                tracing::info!("Reloaded configuration version {}", view.version);
+                metrics::increment("config_reloads_total", &[("result", "success")]);
+            }
+            Err(e) => {
+                tracing::error!("Failed to reload configuration. Error: {}", e);
+                metrics::increment("config_reloads_total", &[("result", "failure")]);
+            }
+        }
+        result
+    }
+
+    /// Reloads if the file has been modified since it was last read.
+    pub fn check(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let modified = modified(path);
+        {
+            let mut active = self.active.lock().unwrap();
+            if modified == active.modified {
+                return;
+            }
+            active.modified = modified;
+        }
+
+        tracing::info!("Config file '{}' changed", path.display());
+        // Failures are logged in reload and retried on the next change.
+        let _ = self.reload();
+    }
+
+    fn try_reload(&self) -> Result<ConfigView, String> {
+        let path = self.path.as_deref().ok_or("no config file to reload")?;
+        let modified = modified(path);
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+
+        let config: Config = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+        config.validate().map_err(|errors| errors.join("; "))?;
+        let pipeline = Pipeline::new(&config, self.key.clone())?;
+        let raw: Map<String, Value> = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+        let config = applied(&raw);
+        let restart_required = restart_required(&raw, &self.started);
+
+        let mut active = self.active.lock().unwrap();
+        active.modified = modified;
+        let digest = digest(&config);
+        if active.view.digest.as_deref() == Some(digest.as_str()) {
+            active.view.restart_required = restart_required;
+            return Ok(active.view.clone());
+        }
+
+        self.pipeline.replace(pipeline);
+        active.view = ConfigView {
+            version: active.view.version + 1,
+            digest: Some(digest),
+            loaded: Utc::now(),
+            config: redact(config),
+            restart_required,
+        };
+        Ok(active.view.clone())
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Digests the applied sections, so edits elsewhere don't change it.
+fn digest(config: &Value) -> String {
+    hex::encode(Sha256::digest(config.to_string().as_bytes()))
+}
+
+fn applied(raw: &Map<String, Value>) -> Value {
+    Value::Object(
+        raw.iter()
+            .filter(|(section, _)| APPLIED.contains(&section.as_str()))
+            .map(|(section, value)| (section.clone(), value.clone()))
+            .collect(),
+    )
+}
+
+/// The sections outside `APPLIED` that differ from the ones started with.
+fn restart_required(raw: &Map<String, Value>, started: &Map<String, Value>) -> Vec<String> {
+    raw.keys()
+        .chain(started.keys())
+        .filter(|section| !APPLIED.contains(&section.as_str()))
+        .filter(|section| raw.get(*section) != started.get(*section))
+        .cloned()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Hides values whose key looks like a credential, at any depth.
+fn redact(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| {
+                    if is_secret(&key) {
+                        (key, Value::String(String::from(REDACTED)))
+                    } else {
+                        (key, redact(value))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.into_iter().map(redact).collect()),
+        other => other,
+    }
+}
+
+fn is_secret(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    key == "key"
+        || key.ends_with("_key")
+        || ["secret", "password", "token"]
+            .iter()
+            .any(|word| key.contains(word))
+}
+
+#[cfg(unix)]
+pub async fn on_hangup(reloader: Arc<Reloader>) {
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        .expect("SIGHUP handler should install");
+    while hangup.recv().await.is_some() {
+        tracing::info!("Received SIGHUP. Reloading configuration");
+        let _ = reloader.reload();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn redacts_secret_values() {
+        let actual = redact(json!({
+            "auth": {"tokens": ["abc"], "api_key": "def"},
+            "dedup": {"key_field": "id"},
+        }));
+        assert_eq!(
+            actual,
+            json!({
+                "auth": {"tokens": "***", "api_key": "***"},
+                "dedup": {"key_field": "id"},
+            })
+        );
+    }
+
+    #[test]
+    fn swaps_pipeline_only_for_valid_config() {
+        let path = std::env::temp_dir().join(format!("config-{}.json", uuid::Uuid::new_v4()));
+        fs::write(&path, "{}").unwrap();
+        let pipeline = Arc::new(SharedPipeline::new(
+            Pipeline::new(&Config::default(), None).unwrap(),
+        ));
+        let reloader = Reloader::new(Some(path.clone()), None, pipeline.clone());
+
+        fs::write(
+            &path,
+            r#"{"redaction":{"fields":[{"pattern":"email","action":"hash"}]}}"#,
+        )
+        .unwrap();
+        assert!(reloader.reload().is_err());
+        assert_eq!(reloader.view().version, 1);
+
+        fs::write(&path, r#"{"reload":{"poll_interval_seconds":0}}"#).unwrap();
+        assert!(reloader.reload().is_err());
+        assert_eq!(reloader.view().version, 1);
+
+        fs::write(&path, r#"{"transform":{"stringify_values":true}}"#).unwrap();
+        let previous = pipeline.current();
+        let actual = reloader.reload().unwrap();
+
+        assert_eq!(actual.version, 2);
+        assert!(actual.restart_required.is_empty());
+        assert!(!Arc::ptr_eq(&previous, &pipeline.current()));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reports_restart_only_changes_without_applying_them() {
+        let path = std::env::temp_dir().join(format!("config-{}.json", uuid::Uuid::new_v4()));
+        fs::write(&path, r#"{"transform":{"stringify_values":true}}"#).unwrap();
+        let pipeline = Arc::new(SharedPipeline::new(
+            Pipeline::new(&Config::default(), None).unwrap(),
+        ));
+        let reloader = Reloader::new(Some(path.clone()), None, pipeline.clone());
+
+        fs::write(
+            &path,
+            r#"{"transform":{"stringify_values":true},"handler":{"concurrency":4}}"#,
+        )
+        .unwrap();
+        let actual = reloader.reload().unwrap();
+
+        assert_eq!(actual.version, 1);
+        assert_eq!(actual.restart_required, vec![String::from("handler")]);
+        assert_eq!(
+            actual.config,
+            json!({"transform": {"stringify_values": true}})
+        );
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -3,7 +3,10 @@ use std::sync::Arc;
 use axum::async_trait;
 use tokio::{sync::broadcast::Receiver, task::JoinHandle, time::Interval};
 
-use crate::{handler::EventHandler, writer::BatchWriter};
+use crate::{
+    handler::EventHandler, processor::Deduplicator, reload::Reloader, server::Certificates,
+    visibility::Heartbeat, writer::BatchWriter,
+};
 
 #[async_trait]
 pub trait Task {
@@ -24,6 +27,34 @@ impl Task for BatchWriter {
     }
 }
 
+#[async_trait]
+impl Task for Heartbeat {
+    async fn run(&self) {
+        self.beat().await;
+    }
+}
+
+#[async_trait]
+impl Task for Deduplicator {
+    async fn run(&self) {
+        self.persist();
+    }
+}
+
+#[async_trait]
+impl Task for Reloader {
+    async fn run(&self) {
+        self.check();
+    }
+}
+
+#[async_trait]
+impl Task for Certificates {
+    async fn run(&self) {
+        self.check().await;
+    }
+}
+
 pub fn task(
     task: Arc<dyn Task + Sync + Send>,
     mut interval: Interval,
@@ -0,0 +1,149 @@
+use std::{
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use axum::Router;
+use axum_server::{tls_rustls::RustlsConfig, Handle};
+use serde::Deserialize;
+
+use crate::metrics;
+
+/// Connections still open after this long are dropped on shutdown. Event
+/// streams never end on their own.
+pub const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+/// Where a listener binds, with PEM files to serve HTTPS from.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListenerConfig {
+    bind: SocketAddr,
+    #[serde(default)]
+    tls: Option<TlsConfig>,
+}
+
+impl ListenerConfig {
+    pub fn new(bind: SocketAddr, tls: Option<TlsConfig>) -> Self {
+        Self { bind, tls }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TlsConfig {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+}
+
+/// A certificate and key that are reloaded when either file changes, so
+/// renewed certificates are served without a restart.
+pub struct Certificates {
+    tls: TlsConfig,
+    rustls: RustlsConfig,
+    modified: Mutex<Option<SystemTime>>,
+}
+
+impl Certificates {
+    pub async fn load(tls: TlsConfig) -> Self {
+        let modified = modified(&tls);
+        let rustls = RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path)
+            .await
+            .expect("TLS certificate and key should be valid PEM files");
+        Self {
+            tls,
+            rustls,
+            modified: Mutex::new(modified),
+        }
+    }
+
+    /// Reloads if either file has been modified since it was last read. A
+    /// file that fails to load is reported and the current one kept.
+    pub async fn check(&self) {
+        let modified = modified(&self.tls);
+        {
+            let mut previous = self.modified.lock().unwrap();
+            if modified == *previous {
+                return;
+            }
+            *previous = modified;
+        }
+
+        let result = self
+            .rustls
+            .reload_from_pem_file(&self.tls.cert_path, &self.tls.key_path)
+            .await;
+        match result {
+            Ok(()) => {
+                tracing::info!(
+                    "Reloaded TLS certificate '{}'",
+                    self.tls.cert_path.display()
+                );
+                metrics::increment("tls_reloads_total", &[("result", "success")]);
+            }
+            Err(e) => {
+                tracing::error!("Failed to reload TLS certificate. Error: {}", e);
+                metrics::increment("tls_reloads_total", &[("result", "failure")]);
+            }
+        }
+    }
+}
+
+/// The later of the two modification times, so a change to either counts.
+fn modified(tls: &TlsConfig) -> Option<SystemTime> {
+    let time = |path: &Path| {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+    time(&tls.cert_path).max(time(&tls.key_path))
+}
+
+pub struct Server {
+    bind: SocketAddr,
+    certificates: Option<Arc<Certificates>>,
+}
+
+impl Server {
+    pub async fn new(config: &ListenerConfig) -> Self {
+        let certificates = match &config.tls {
+            Some(tls) => Some(Arc::new(Certificates::load(tls.clone()).await)),
+            None => None,
+        };
+        Self {
+            bind: config.bind,
+            certificates,
+        }
+    }
+
+    pub fn certificates(&self) -> Option<Arc<Certificates>> {
+        self.certificates.clone()
+    }
+
+    /// Serves until the handle is shut down, over HTTPS when TLS is configured.
+    pub async fn serve(self, app: Router, handle: Handle) {
+        let scheme = if self.certificates.is_some() {
+            "https"
+        } else {
+            "http"
+        };
+        tracing::info!("Listening on {}://{}", scheme, self.bind);
+
+        let service = app.into_make_service();
+        let result = match self.certificates {
+            Some(certificates) => {
+                axum_server::bind_rustls(self.bind, certificates.rustls.clone())
+                    .handle(handle)
+                    .serve(service)
+                    .await
+            }
+            None => {
+                axum_server::bind(self.bind)
+                    .handle(handle)
+                    .serve(service)
+                    .await
+            }
+        };
+        result.expect("Server should bind and serve");
+    }
+}
@@ -2,11 +2,12 @@ use std::sync::Arc;
 
 use tokio::{sync::broadcast::Sender, task::JoinHandle};
 
-use crate::writer::BatchWriter;
+use crate::{processor::Deduplicator, writer::BatchWriter};
 
 pub async fn hook(
     shutdown_sender: Sender<()>,
     batch_writer: Arc<BatchWriter>,
+    deduplicator: Arc<Deduplicator>,
     background_tasks: Vec<JoinHandle<()>>,
 ) {
     signal().await;
@@ -19,6 +20,7 @@ pub async fn hook(
         task.await.unwrap();
     }
     batch_writer.flush().await;
+    deduplicator.persist();
 }
 
 async fn signal() {
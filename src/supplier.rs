@@ -1,38 +1,53 @@
-use aws_sdk_sqs::{types::Message, Client};
+use std::time::Duration;
+
+use aws_sdk_sqs::{types::Message as SqsMessage, Client};
 use axum::async_trait;
 
-use crate::model::{Record, S3Notification};
+use crate::{
+    metrics,
+    model::{Envelope, EventBridgeNotification, EventType, Message, Record},
+};
 
 use super::model::Notification;
 
 #[async_trait]
 pub trait Supplier {
-    async fn get(&self) -> Vec<Notification>;
+    async fn get(&self) -> Vec<Message>;
 }
 
 pub struct SqsSupplier {
     client: Client,
     queue_url: String,
+    visibility_timeout: Duration,
+    event_types: Vec<EventType>,
 }
 
 impl SqsSupplier {
-    pub fn new(client: Client, queue_url: &str) -> Self {
+    pub fn new(
+        client: Client,
+        queue_url: &str,
+        visibility_timeout: Duration,
+        event_types: &[EventType],
+    ) -> Self {
         Self {
             client,
             queue_url: String::from(queue_url),
+            visibility_timeout,
+            event_types: event_types.to_vec(),
         }
     }
 }
 
 #[async_trait]
 impl Supplier for SqsSupplier {
-    async fn get(&self) -> Vec<Notification> {
+    async fn get(&self) -> Vec<Message> {
         let response = self
             .client
             .receive_message()
             .queue_url(&self.queue_url)
             .max_number_of_messages(10)
             .wait_time_seconds(5)
+            .visibility_timeout(self.visibility_timeout.as_secs() as i32)
             .send()
             .await
             .unwrap();
@@ -45,26 +60,196 @@ impl Supplier for SqsSupplier {
         response
             .messages()
             .iter()
-            .flat_map(notifications_from)
+            .filter_map(|message| message_from(message, &self.event_types))
             .collect()
     }
 }
 
-fn notifications_from(message: &Message) -> Vec<Notification> {
-    let s3_notification: S3Notification = serde_json::from_str(message.body().unwrap()).unwrap();
-    s3_notification
-        .records()
-        .iter()
-        .map(|record| notification_from(record, message))
-        .collect()
+// Unreadable messages are left on the queue so the redrive policy can move
+// them aside once they have been received too many times.
+fn message_from(message: &SqsMessage, event_types: &[EventType]) -> Option<Message> {
+    let message_id = message.message_id().unwrap();
+    let notifications = serde_json::from_str(message.body().unwrap_or_default())
+        .and_then(|envelope| notifications_in(&envelope, message, event_types));
+
+    match notifications {
+        Ok(notifications) => Some(Message::new(
+            message_id,
+            message.receipt_handle().unwrap(),
+            notifications,
+        )),
+        Err(e) => {
+            tracing::error!("Unable to read message '{}': {}", message_id, e);
+            metrics::increment("messages_unreadable_total", &[]);
+            None
+        }
+    }
+}
+
+fn notifications_in(
+    envelope: &Envelope,
+    message: &SqsMessage,
+    event_types: &[EventType],
+) -> Result<Vec<Notification>, serde_json::Error> {
+    let notifications = match envelope {
+        Envelope::S3(s3_notification) => s3_notification
+            .records()
+            .iter()
+            .filter(|record| accepts(record.event_type(), event_types))
+            .map(|record| notification_from(record, message))
+            .collect(),
+        Envelope::Test(test_event) => {
+            tracing::info!(
+                "Acknowledging '{}' for bucket '{}'",
+                test_event.event(),
+                test_event.bucket()
+            );
+            metrics::increment("test_events_total", &[("bucket", test_event.bucket())]);
+            Vec::new()
+        }
+        Envelope::Sns(sns_notification) => {
+            tracing::debug!(
+                "Unwrapping message '{}' from topic '{}'",
+                message.message_id().unwrap_or_default(),
+                sns_notification.topic_arn()
+            );
+            let inner: Envelope = serde_json::from_str(sns_notification.message())?;
+            notifications_in(&inner, message, event_types)?
+        }
+        Envelope::EventBridge(event) => {
+            let event_type = EventType::from_detail_type(event.detail_type());
+            if accepts(&event_type, event_types) {
+                vec![notification_from_event_bridge(event, message)]
+            } else {
+                Vec::new()
+            }
+        }
+    };
+    Ok(notifications)
+}
+
+fn accepts(event_type: &EventType, event_types: &[EventType]) -> bool {
+    if event_types.contains(event_type) {
+        return true;
+    }
+
+    tracing::info!("Skipping '{}' event", event_type);
+    metrics::increment(
+        "notifications_skipped_total",
+        &[("event_type", &event_type.to_string())],
+    );
+    false
 }
 
-fn notification_from(record: &Record, message: &Message) -> Notification {
+fn notification_from(record: &Record, message: &SqsMessage) -> Notification {
     Notification::builder()
         .message_id(message.message_id().unwrap())
         .receipt_handle(message.receipt_handle().unwrap())
         .created(*record.event_time())
         .bucket(record.s3().bucket().name())
         .key(record.s3().object().key())
+        .size(record.s3().object().size())
+        .e_tag(record.s3().object().e_tag())
+        .version_id(record.s3().object().version_id())
+        .sequencer(record.s3().object().sequencer())
         .build()
 }
+
+fn notification_from_event_bridge(
+    event: &EventBridgeNotification,
+    message: &SqsMessage,
+) -> Notification {
+    Notification::builder()
+        .message_id(message.message_id().unwrap())
+        .receipt_handle(message.receipt_handle().unwrap())
+        .created(*event.time())
+        .bucket(event.detail().bucket().name())
+        .key(event.detail().object().key())
+        .size(event.detail().object().size())
+        .e_tag(event.detail().object().e_tag())
+        .version_id(event.detail().object().version_id())
+        .sequencer(event.detail().object().sequencer())
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_s3_notification() {
+        let actual = read(&message(
+            r#"{"Records":[{"eventTime":"2024-08-10T12:53:00.000Z","eventName":"ObjectCreated:Put","s3":{"bucket":{"name":"test-bucket"},"object":{"key":"1234.json"}}}]}"#,
+        ));
+        assert_eq!(keys(&actual), vec!["test-bucket/1234.json"])
+    }
+
+    #[test]
+    fn reads_decoded_key_and_version() {
+        let actual = read(&message(
+            r#"{"Records":[{"eventTime":"2024-08-10T12:53:00.000Z","eventName":"ObjectCreated:Put","s3":{"bucket":{"name":"test-bucket"},"object":{"key":"input/caf%C3%A9+menu.json","size":12,"versionId":"v2"}}}]}"#,
+        ));
+        let notification = &actual.notifications()[0];
+        assert_eq!(notification.key(), "input/café menu.json");
+        assert_eq!(notification.size(), Some(12));
+        assert_eq!(notification.version_id(), Some("v2"));
+    }
+
+    #[test]
+    fn unwraps_sns_notification() {
+        let actual = read(&message(
+            r#"{"Type":"Notification","TopicArn":"arn:aws:sns:us-east-1:000000000000:test-topic","Message":"{\"Records\":[{\"eventTime\":\"2024-08-10T12:53:00.000Z\",\"eventName\":\"ObjectCreated:Put\",\"s3\":{\"bucket\":{\"name\":\"test-bucket\"},\"object\":{\"key\":\"1234.json\"}}}]}"}"#,
+        ));
+        assert_eq!(keys(&actual), vec!["test-bucket/1234.json"])
+    }
+
+    #[test]
+    fn reads_event_bridge_notification() {
+        let actual = read(&message(
+            r#"{"detail-type":"Object Created","source":"aws.s3","time":"2024-08-10T12:53:00Z","detail":{"bucket":{"name":"test-bucket"},"object":{"key":"1234.json"}}}"#,
+        ));
+        assert_eq!(keys(&actual), vec!["test-bucket/1234.json"])
+    }
+
+    #[test]
+    fn acknowledges_test_event() {
+        let actual = read(&message(
+            r#"{"Service":"Amazon S3","Event":"s3:TestEvent","Time":"2024-08-10T12:53:00.000Z","Bucket":"test-bucket"}"#,
+        ));
+        assert!(actual.notifications().is_empty())
+    }
+
+    #[test]
+    fn skips_unconfigured_event_types() {
+        let actual = read(&message(
+            r#"{"Records":[{"eventTime":"2024-08-10T12:53:00.000Z","eventName":"ObjectRemoved:Delete","s3":{"bucket":{"name":"test-bucket"},"object":{"key":"1234.json"}}}]}"#,
+        ));
+        assert!(actual.notifications().is_empty())
+    }
+
+    #[test]
+    fn does_not_read_unknown_message() {
+        let actual = message_from(&message(r#"{"unknown":true}"#), &[EventType::ObjectCreated]);
+        assert!(actual.is_none())
+    }
+
+    fn read(message: &SqsMessage) -> Message {
+        message_from(message, &[EventType::ObjectCreated]).unwrap()
+    }
+
+    fn keys(message: &Message) -> Vec<String> {
+        message
+            .notifications()
+            .iter()
+            .map(|notification| format!("{}/{}", notification.bucket(), notification.key()))
+            .collect()
+    }
+
+    fn message(body: &str) -> SqsMessage {
+        SqsMessage::builder()
+            .message_id("some-message")
+            .receipt_handle("receipt")
+            .body(body)
+            .build()
+    }
+}
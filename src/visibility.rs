@@ -0,0 +1,253 @@
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use aws_sdk_sqs::{types::ChangeMessageVisibilityBatchRequestEntry, Client};
+use axum::async_trait;
+use tokio::sync::Mutex;
+
+const MAX_BATCH_SIZE: usize = 10;
+
+#[async_trait]
+pub trait VisibilityChanger {
+    async fn change_visibility(&self, receipt_handles: &[String], timeout: Duration);
+}
+
+pub struct SqsVisibilityChanger {
+    client: Client,
+    queue_url: String,
+}
+
+impl SqsVisibilityChanger {
+    pub fn new(client: Client, queue_url: &str) -> Self {
+        Self {
+            client,
+            queue_url: String::from(queue_url),
+        }
+    }
+}
+
+#[async_trait]
+impl VisibilityChanger for SqsVisibilityChanger {
+    async fn change_visibility(&self, receipt_handles: &[String], timeout: Duration) {
+        for chunk in receipt_handles.chunks(MAX_BATCH_SIZE) {
+            let entries = chunk
+                .iter()
+                .enumerate()
+                .map(|(idx, receipt_handle)| {
+                    ChangeMessageVisibilityBatchRequestEntry::builder()
+                        .id(idx.to_string())
+                        .receipt_handle(receipt_handle)
+                        .visibility_timeout(timeout.as_secs() as i32)
+                        .build()
+                        .unwrap()
+                })
+                .collect();
+
+            let result = self
+                .client
+                .change_message_visibility_batch()
+                .queue_url(&self.queue_url)
+                .set_entries(Some(entries))
+                .send()
+                .await;
+
+            // The next beat tries again, so a failed request is only reported.
+            let output = match result {
+                Ok(output) => output,
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to change visibility of {} messages on '{}'. Error: {}",
+                        chunk.len(),
+                        self.queue_url,
+                        e
+                    );
+                    continue;
+                }
+            };
+            for failure in output.failed() {
+                tracing::warn!(
+                    "Failed to change visibility of entry {} on '{}': {} {}",
+                    failure.id(),
+                    self.queue_url,
+                    failure.code(),
+                    failure.message().unwrap_or_default()
+                );
+            }
+        }
+    }
+}
+
+/// Keeps in-flight messages invisible on the queue until they are deleted or
+/// released.
+pub struct Heartbeat {
+    changer: Arc<dyn VisibilityChanger + Send + Sync>,
+    in_flight: Mutex<HashSet<String>>,
+    // Held across visibility changes, never by track or untrack, so a slow
+    // SQS call doesn't hold up the receive loops.
+    changing: Mutex<()>,
+    timeout: Duration,
+    release_delay: Duration,
+}
+
+impl Heartbeat {
+    pub fn new(
+        changer: Arc<dyn VisibilityChanger + Send + Sync>,
+        timeout: Duration,
+        release_delay: Duration,
+    ) -> Self {
+        Self {
+            changer,
+            in_flight: Mutex::new(HashSet::new()),
+            changing: Mutex::new(()),
+            timeout,
+            release_delay,
+        }
+    }
+
+    pub async fn track(&self, receipt_handles: &[String]) {
+        self.in_flight
+            .lock()
+            .await
+            .extend(receipt_handles.iter().cloned());
+    }
+
+    pub async fn untrack(&self, receipt_handles: &[String]) {
+        let mut in_flight = self.in_flight.lock().await;
+        for receipt_handle in receipt_handles {
+            in_flight.remove(receipt_handle);
+        }
+    }
+
+    /// Stops extending the given messages and makes them visible again after
+    /// the configured release delay so they are retried.
+    pub async fn release(&self, receipt_handles: &[String]) {
+        if receipt_handles.is_empty() {
+            return;
+        }
+
+        // Waits for a beat in progress, which may have copied these handles,
+        // so it cannot extend a message straight after it has been released.
+        let _changing = self.changing.lock().await;
+        self.untrack(receipt_handles).await;
+        tracing::info!(
+            "Releasing {} messages with visibility timeout {}s",
+            receipt_handles.len(),
+            self.release_delay.as_secs()
+        );
+        self.changer
+            .change_visibility(receipt_handles, self.release_delay)
+            .await;
+    }
+
+    pub async fn beat(&self) {
+        let _changing = self.changing.lock().await;
+        let receipt_handles: Vec<String> = self.in_flight.lock().await.iter().cloned().collect();
+        if receipt_handles.is_empty() {
+            return;
+        }
+
+        tracing::info!(
+            "Extending visibility of {} in-flight messages by {}s",
+            receipt_handles.len(),
+            self.timeout.as_secs()
+        );
+        self.changer
+            .change_visibility(&receipt_handles, self.timeout)
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex as StdMutex;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn extends_only_in_flight_messages() {
+        let changer = Arc::new(RecordingChanger::default());
+        let heartbeat = heartbeat(changer.clone());
+
+        heartbeat.track(&handles(&["a", "b"])).await;
+        heartbeat.untrack(&handles(&["a"])).await;
+        heartbeat.beat().await;
+
+        let changes = changer.changes.lock().unwrap();
+        assert_eq!(*changes, vec![(handles(&["b"]), Duration::from_secs(30))]);
+    }
+
+    #[tokio::test]
+    async fn released_messages_are_no_longer_extended() {
+        let changer = Arc::new(RecordingChanger::default());
+        let heartbeat = heartbeat(changer.clone());
+
+        heartbeat.track(&handles(&["a"])).await;
+        heartbeat.release(&handles(&["a"])).await;
+        heartbeat.beat().await;
+
+        let changes = changer.changes.lock().unwrap();
+        assert_eq!(*changes, vec![(handles(&["a"]), Duration::ZERO)]);
+    }
+
+    #[tokio::test]
+    async fn tracks_messages_while_a_beat_is_slow() {
+        let changer = Arc::new(BlockedChanger::default());
+        let heartbeat = Arc::new(Heartbeat::new(
+            changer.clone(),
+            Duration::from_secs(30),
+            Duration::ZERO,
+        ));
+        heartbeat.track(&handles(&["a"])).await;
+
+        let beat = tokio::spawn({
+            let heartbeat = heartbeat.clone();
+            async move { heartbeat.beat().await }
+        });
+        changer.started.notified().await;
+        let tracked = tokio::time::timeout(Duration::from_secs(1), async {
+            heartbeat.track(&handles(&["b"])).await;
+            heartbeat.untrack(&handles(&["a"])).await;
+        })
+        .await;
+
+        assert!(tracked.is_ok());
+        changer.finish.notify_one();
+        beat.await.unwrap();
+    }
+
+    #[derive(Default)]
+    struct BlockedChanger {
+        started: tokio::sync::Notify,
+        finish: tokio::sync::Notify,
+    }
+
+    #[async_trait]
+    impl VisibilityChanger for BlockedChanger {
+        async fn change_visibility(&self, _receipt_handles: &[String], _timeout: Duration) {
+            self.started.notify_one();
+            self.finish.notified().await;
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingChanger {
+        changes: StdMutex<Vec<(Vec<String>, Duration)>>,
+    }
+
+    #[async_trait]
+    impl VisibilityChanger for RecordingChanger {
+        async fn change_visibility(&self, receipt_handles: &[String], timeout: Duration) {
+            self.changes
+                .lock()
+                .unwrap()
+                .push((receipt_handles.to_vec(), timeout));
+        }
+    }
+
+    fn heartbeat(changer: Arc<RecordingChanger>) -> Heartbeat {
+        Heartbeat::new(changer, Duration::from_secs(30), Duration::ZERO)
+    }
+
+    fn handles(receipt_handles: &[&str]) -> Vec<String> {
+        receipt_handles.iter().map(|h| String::from(*h)).collect()
+    }
+}
@@ -1,17 +1,69 @@
 use std::sync::{Arc, LazyLock};
 
-use chrono::{TimeDelta, Utc};
+use chrono::{NaiveDate, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use utoipa::{IntoParams, ToSchema};
 
-use crate::batch::{self, Batch};
+use crate::{
+    activity::{self, Activity},
+    batch::{self, Batch},
+    metrics,
+};
 
 use super::Writer;
 
 const MAX_BATCH_SIZE: usize = 1;
 static MAX_BATCH_AGE: LazyLock<TimeDelta> = LazyLock::new(|| TimeDelta::minutes(60));
 
+/// Limits a manual flush to batches of one source and/or date.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FlushFilter {
+    source: Option<String>,
+    date: Option<NaiveDate>,
+}
+
+impl FlushFilter {
+    fn matches(&self, batch: &Batch) -> bool {
+        let partition = batch.partition();
+        let source_matches = match &self.source {
+            Some(source) => source == partition.source(),
+            None => true,
+        };
+        let date_matches = match &self.date {
+            Some(date) => date == partition.date(),
+            None => true,
+        };
+        source_matches && date_matches
+    }
+}
+
+/// The outcome of writing one batch. A failed batch keeps its records for the
+/// next attempt.
+#[derive(Debug, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Written {
+    source: String,
+    date: NaiveDate,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    records: usize,
+}
+
+impl Written {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
 pub struct BatchWriter {
     batch_store: Arc<dyn batch::Store + Sync + Send>,
     writer: Box<dyn Writer + Sync + Send>,
+    // Held while writing so a manual flush and the scheduled task never write
+    // the same records twice.
+    writing: Mutex<()>,
 }
 
 impl BatchWriter {
@@ -22,22 +74,73 @@ impl BatchWriter {
         Self {
             batch_store,
             writer,
+            writing: Mutex::new(()),
         }
     }
 
     pub async fn write(&self) {
+        let _writing = self.writing.lock().await;
         for batch in self.batch_store.batches() {
             if is_ready(&batch) {
-                self.writer.write(&batch).await;
-                self.batch_store.delete_batch(batch.partition());
+                self.write_batch(&batch).await;
             }
         }
     }
 
+    /// Writes every matching batch whether or not it is ready.
+    pub async fn flush_matching(&self, filter: &FlushFilter) -> Vec<Written> {
+        let _writing = self.writing.lock().await;
+        let mut written = Vec::new();
+        for batch in self.batch_store.batches() {
+            if filter.matches(&batch) {
+                written.push(self.write_batch(&batch).await);
+            }
+        }
+        written
+    }
+
     pub async fn flush(&self) {
         tracing::info!("Writing all batches prior to shutdown...");
-        for batch in self.batch_store.batches() {
-            self.writer.write(&batch).await;
+        self.flush_matching(&FlushFilter::default()).await;
+    }
+
+    async fn write_batch(&self, batch: &Batch) -> Written {
+        let source = String::from(batch.partition().source());
+        let date = *batch.partition().date();
+        let records = batch.record_count();
+
+        match self.writer.write(batch).await {
+            Ok(key) => {
+                self.batch_store.delete_records(batch.partition(), records);
+                activity::publish(Activity::BatchFlushed {
+                    source: source.clone(),
+                    date,
+                    key: key.clone(),
+                    records,
+                });
+                Written {
+                    source,
+                    date,
+                    key: Some(key),
+                    error: None,
+                    records,
+                }
+            }
+            Err(e) => {
+                tracing::error!(
+                    "Failed to write batch '{:?}'. Keeping its records. Error: {}",
+                    batch.partition(),
+                    e
+                );
+                metrics::increment("batch_write_failures_total", &[("source", &source)]);
+                Written {
+                    source,
+                    date,
+                    key: None,
+                    error: Some(e),
+                    records,
+                }
+            }
         }
     }
 }
@@ -65,3 +168,89 @@ fn is_ready(batch: &Batch) -> bool {
     };
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::async_trait;
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::batch::{Entry, Partition, Store, StoreImpl};
+
+    #[tokio::test]
+    async fn flushes_only_matching_batches() {
+        let store = Arc::new(StoreImpl::new());
+        let date = NaiveDate::from_ymd_opt(2024, 8, 1).unwrap();
+        store.add(Entry::new(
+            Partition::new("somewhere", date),
+            &Utc::now(),
+            "message-1",
+            "1",
+        ));
+        store.add(Entry::new(
+            Partition::new("somewhere", date),
+            &Utc::now(),
+            "message-2",
+            "2",
+        ));
+        store.add(Entry::new(
+            Partition::new("elsewhere", date),
+            &Utc::now(),
+            "message-3",
+            "3",
+        ));
+        let writer = BatchWriter::new(store.clone(), Box::new(KeyWriter));
+
+        let actual = writer
+            .flush_matching(&FlushFilter {
+                source: Some(String::from("somewhere")),
+                date: Some(date),
+            })
+            .await;
+
+        assert_eq!(
+            actual,
+            vec![Written {
+                source: String::from("somewhere"),
+                date,
+                key: Some(String::from("somewhere/2024-08-01")),
+                error: None,
+                records: 2,
+            }]
+        );
+        let remaining = store.batches();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].partition().source(), "elsewhere");
+    }
+
+    #[tokio::test]
+    async fn keeps_records_of_failed_writes() {
+        let store = Arc::new(StoreImpl::new());
+        let date = NaiveDate::from_ymd_opt(2024, 8, 1).unwrap();
+        store.add(Entry::new(
+            Partition::new("unwritable", date),
+            &Utc::now(),
+            "message-1",
+            "1",
+        ));
+        let writer = BatchWriter::new(store.clone(), Box::new(KeyWriter));
+
+        let actual = writer.flush_matching(&FlushFilter::default()).await;
+
+        assert_eq!(actual[0].error, Some(String::from("unavailable")));
+        assert!(!actual[0].is_ok());
+        assert_eq!(store.batches()[0].record_count(), 1);
+    }
+
+    struct KeyWriter;
+
+    #[async_trait]
+    impl Writer for KeyWriter {
+        async fn write(&self, batch: &Batch) -> Result<String, String> {
+            match batch.partition().source() {
+                "unwritable" => Err(String::from("unavailable")),
+                source => Ok(format!("{}/{}", source, batch.partition().date())),
+            }
+        }
+    }
+}
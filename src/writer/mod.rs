@@ -4,11 +4,12 @@ use axum::async_trait;
 mod batch;
 
 use crate::batch::{Batch, Partition};
-pub use batch::BatchWriter;
+pub use batch::{BatchWriter, FlushFilter, Written};
 
 #[async_trait]
 pub trait Writer {
-    async fn write(&self, batch: &Batch);
+    /// Writes the batch and returns the key it was written to.
+    async fn write(&self, batch: &Batch) -> Result<String, String>;
 }
 
 pub struct S3Writer {
@@ -38,7 +39,7 @@ impl S3Writer {
 
 #[async_trait]
 impl Writer for S3Writer {
-    async fn write(&self, batch: &Batch) {
+    async fn write(&self, batch: &Batch) -> Result<String, String> {
         let key = self.key(batch.partition());
         let content = file_content_from(batch.records());
 
@@ -51,11 +52,12 @@ impl Writer for S3Writer {
         self.client
             .put_object()
             .bucket(&self.bucket)
-            .key(key)
+            .key(&key)
             .body(ByteStream::new(content.into()))
             .send()
             .await
-            .unwrap();
+            .map_err(|e| e.to_string())?;
+        Ok(key)
     }
 }
 